@@ -1,7 +1,13 @@
 use super::*;
 use crate::data::{
-    rank as rank_model, tag as tag_model, torrent_info as torrent_info_model, user as user_model,
-    user_info as user_info_model,
+    hit_and_run as hit_and_run_model,
+    hit_and_run::HitAndRunStatus,
+    ip_ban as ip_ban_model, ledger as ledger_model,
+    ledger::{LedgerReason, MoneyChange},
+    promotion as promotion_model, rank as rank_model, ratio_watch as ratio_watch_model,
+    shop as shop_model, site_event as site_event_model, stat as stat_model,
+    subscription as subscription_model, tag as tag_model, torrent_info as torrent_info_model,
+    user as user_model,
 };
 
 #[get("/show_invisible_torrents")]
@@ -24,16 +30,111 @@ async fn accept_torrents(
     if is_no_permission_to_torrents(claim.role) {
         return Err(Error::NoPermission);
     }
-    let ret = torrent_info_model::make_torrent_visible(&client, &data.ids).await?;
+    // matches are committed with the torrents, so that a failed notification
+    // is left to the job rather than lost
+    let mut tx = client.begin().await?;
+    let ret = torrent_info_model::make_torrent_visible(&mut tx, &data.ids).await?;
 
+    let mut torrents = vec![];
     for torrent in ret {
-        for tag in torrent.tag.unwrap_or_default() {
-            tag_model::update_or_add_tag(&client, &tag).await?;
+        let tags = torrent.tag.unwrap_or_default();
+        for tag in &tags {
+            tag_model::update_or_add_tag(&mut tx, tag).await?;
         }
+        torrents.push((torrent.id, torrent.title, torrent.poster, tags));
+    }
+    let to_notify = match_subscribers(&mut tx, &torrents).await?;
+    tx.commit().await?;
+
+    // subscribers are notified in background
+    if !to_notify.is_empty() {
+        let client = client.get_ref().clone();
+        actix_web::rt::spawn(async move {
+            notify_subscribers(&client, to_notify).await;
+        });
     }
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
 
+/// Match accepted torrents of (id, title, poster, tags) against subscriptions,
+/// searched by the same tokens as the site search, and record the matches.
+/// Users are not matched with their own torrents. It returns users having new
+/// matches and not in digest mode.
+async fn match_subscribers(
+    tx: &mut sqlx::PgConnection,
+    torrents: &[(i64, String, String, Vec<String>)],
+) -> Result<Vec<i64>, Error> {
+    use crate::search::match_subscriptions;
+
+    if torrents.is_empty() {
+        return Ok(vec![]);
+    }
+    // a subscription matches only if the torrent has all of its tags
+    let mut tags: Vec<String> = torrents
+        .iter()
+        .flat_map(|(_, _, _, tags)| tags.iter().cloned())
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+    let subscriptions = subscription_model::find_active_subscriptions(&mut *tx, &tags).await?;
+    if subscriptions.is_empty() {
+        return Ok(vec![]);
+    }
+    let filters: Vec<(&[String], &[String])> = subscriptions
+        .iter()
+        .map(|s| (s.keywords.as_slice(), s.tags.as_slice()))
+        .collect();
+    let searched: Vec<(i64, Vec<String>, Vec<String>)> = torrents
+        .iter()
+        .map(|(id, title, poster, tags)| {
+            let mut tokens = vec![title.clone(), poster.clone()];
+            tokens.extend(tags.iter().cloned());
+            (*id, tokens, tags.clone())
+        })
+        .collect();
+
+    let matched = match_subscriptions(&searched, &filters);
+    let mut matches = vec![];
+    for (subscription, ids) in subscriptions.iter().zip(matched) {
+        for id in ids {
+            let own = torrents
+                .iter()
+                .any(|(tid, _, poster, _)| *tid == id && *poster == subscription.username);
+            if !own {
+                matches.push((subscription.uid, subscription.id, id));
+            }
+        }
+    }
+    if matches.is_empty() {
+        return Ok(vec![]);
+    }
+
+    Ok(subscription_model::add_matches(&mut *tx, &matches)
+        .await?
+        .into_iter()
+        .filter(|uid| !subscriptions.iter().any(|s| s.uid == *uid && s.digest))
+        .collect())
+}
+
+/// Notify users of their recorded matches, those failed to notify are left to the job.
+async fn notify_subscribers(client: &sqlx::PgPool, uids: Vec<i64>) {
+    let hourly_limit = match KVDB
+        .clone()
+        .get_number("config", "SUBSCRIPTION HOURLY LIMIT".as_ref())
+    {
+        Ok(limit) => limit.unwrap_or(20),
+        Err(e) => {
+            log::error!("notify subscribers failed: {:?}", e);
+            return;
+        }
+    };
+    for uid in uids {
+        if let Err(e) = subscription_model::notify_matches(client, uid, Some(hourly_limit)).await {
+            log::warn!("notify subscriber {} failed: {:?}", uid, e);
+        }
+    }
+}
+
 #[post("/stick_torrents")]
 async fn stick_torrents(
     data: web::Json<IdsWrapper>,
@@ -62,9 +163,10 @@ async fn unstick_torrents(
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
 
-#[post("/free_torrents")]
-async fn free_torrents(
-    data: web::Json<IdsWrapper>,
+/// set promotion of torrents, `None` removes it
+#[post("/promote_torrents")]
+async fn promote_torrents(
+    data: web::Json<PromotionRequest>,
     req: HttpRequest,
     client: web::Data<sqlx::PgPool>,
 ) -> HttpResult {
@@ -72,21 +174,13 @@ async fn free_torrents(
     if is_no_permission_to_torrents(claim.role) {
         return Err(Error::NoPermission);
     }
-    torrent_info_model::make_torrent_free(&client, &data.ids).await?;
-    Ok(HttpResponse::Ok().json(GeneralResponse::default()))
-}
-
-#[post("/unfree_torrents")]
-async fn unfree_torrents(
-    data: web::Json<IdsWrapper>,
-    req: HttpRequest,
-    client: web::Data<sqlx::PgPool>,
-) -> HttpResult {
-    let claim = get_info_in_token(&req)?;
-    if is_no_permission_to_torrents(claim.role) {
-        return Err(Error::NoPermission);
+    if let (Some(start), Some(end)) = (data.start, data.end) {
+        if start >= end {
+            return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("end before start")));
+        }
     }
-    torrent_info_model::make_torrent_unfree(&client, &data.ids).await?;
+    promotion_model::promote_torrents(&client, &data.ids, data.promotion, data.start, data.end)
+        .await?;
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
 
@@ -101,6 +195,7 @@ async fn ban_user(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResu
     let old_key = user_model::find_user_by_id(&client, data.id).await?.passkey;
     update_passkey_filter(None, Some(old_key)).await?;
     user_model::delete_role_by_id(&client, data.id, 0).await?;
+    ratio_watch_model::delete_ratio_watch_by_uid(&client, data.id).await?;
 
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
@@ -142,7 +237,14 @@ async fn group_awards(
     data.ids.sort_unstable();
     data.ids.dedup();
 
-    user_info_model::award_money_by_id(&client, &data.ids, data.amount).await?;
+    let changes = data
+        .ids
+        .iter()
+        .map(|id| {
+            MoneyChange::new(*id, data.amount, LedgerReason::AdminGrant).counterparty(&claim.sub)
+        })
+        .collect();
+    ledger_model::change_money(&client, changes).await?;
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
 
@@ -162,6 +264,9 @@ async fn change_permission(
     }
     for permission in &data.take {
         user_model::delete_role_by_id(&client, data.id, permission % 64).await?;
+        if permission % 64 == 0 {
+            ratio_watch_model::delete_ratio_watch_by_uid(&client, data.id).await?;
+        }
     }
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
@@ -174,16 +279,160 @@ async fn award_rank(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpRe
     }
 
     let data = deserialize_from_req!(req, RankAwardRequest);
-    let rank = rank_model::find_rank_by_id(&client, data.rid).await?;
-    let roles = rank.role;
-    for role in roles {
-        user_model::add_role_by_id(&client, data.uid, (role % 32) as i32).await?;
+    let from = rank_model::find_rank_by_uid(&client, data.uid).await?;
+    let to = rank_model::find_rank_by_id(&client, data.rid).await?;
+    let reason = format!("awarded by {}", claim.sub);
+    rank_model::change_rank(
+        &client,
+        data.uid,
+        &from,
+        &to,
+        &reason,
+        data.locked.unwrap_or(true),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GeneralResponse::default()))
+}
+
+#[get("/list_rank_logs")]
+async fn list_rank_logs(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_users(claim.role) {
+        return Err(Error::NoPermission);
     }
-    user_info_model::update_rank_by_id(&client, data.uid, rank.id).await?;
+    let id = deserialize_from_req!(req, IdWrapper).id;
+    let ret = rank_model::find_rank_logs_by_uid(&client, id).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// list hit and runs, only marked ones by default
+#[get("/list_hit_and_runs")]
+async fn list_hit_and_runs(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_users(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let data = deserialize_from_req!(req, HitAndRunListRequest);
+    let status = data.status.unwrap_or(HitAndRunStatus::Marked as i32);
+    let ret = hit_and_run_model::find_hit_and_runs_by_status(&client, status).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[get("/list_ratio_watches")]
+async fn list_ratio_watches(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_users(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let ret = ratio_watch_model::list_ratio_watches(&client).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
 
+#[get("/list_money_ledger")]
+async fn list_money_ledger(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_users(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let data = deserialize_from_req!(req, LedgerRequest);
+    let id = match data.id {
+        Some(id) => id,
+        None => return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("id is required"))),
+    };
+    let page = data.page.unwrap_or(0);
+    let count = ledger_model::query_ledger_counts_by_uid(&client, id).await?;
+    let ret = ledger_model::find_ledger_by_uid(&client, id, (page * 20) as i64).await?;
+    let resp = DataWithCount::new(serde_json::to_value(ret).unwrap(), count / 20 + 1);
+    Ok(HttpResponse::Ok().json(resp.to_json()))
+}
+
+#[post("/pardon_hit_and_runs")]
+async fn pardon_hit_and_runs(
+    data: web::Json<IdsWrapper>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_users(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    hit_and_run_model::settle_hit_and_runs(
+        &client,
+        &data.ids,
+        HitAndRunStatus::Pardoned,
+        &claim.sub,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(GeneralResponse::default()))
+}
+
+/// deduct `HNR PENALTY` from user for each hit and run
+#[post("/punish_hit_and_runs")]
+async fn punish_hit_and_runs(
+    data: web::Json<IdsWrapper>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_users(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let penalty = KVDB
+        .clone()
+        .get_float("config", "HNR PENALTY".as_ref())?
+        .unwrap();
+    let uids = hit_and_run_model::settle_hit_and_runs(
+        &client,
+        &data.ids,
+        HitAndRunStatus::Punished,
+        &claim.sub,
+    )
+    .await?;
+    let changes = uids
+        .into_iter()
+        .map(|uid| MoneyChange::new(uid, -penalty, LedgerReason::Penalty).counterparty(&claim.sub))
+        .collect();
+    ledger_model::change_money(&client, changes).await?;
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
 
+#[get("/list_hit_and_run_rules")]
+async fn list_hit_and_run_rules(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_torrents(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let ret = hit_and_run_model::list_hit_and_run_rules(&client).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// override `HNR *` site settings for a torrent, the rule is removed if none is set
+#[post("/update_hit_and_run_rule")]
+async fn update_hit_and_run_rule(
+    data: web::Json<HitAndRunRuleRequest>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_torrents(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    if data.min_seed_hours.is_none() && data.min_ratio.is_none() && data.grace_days.is_none() {
+        hit_and_run_model::delete_hit_and_run_rule(&client, data.tid).await?;
+        return Ok(HttpResponse::Ok().json(GeneralResponse::default()));
+    }
+    let ret = hit_and_run_model::update_or_add_hit_and_run_rule(
+        &client,
+        data.tid,
+        data.min_seed_hours,
+        data.min_ratio,
+        data.grace_days,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
 #[get("/get_email_whitelist")]
 async fn get_email_whitelist(req: HttpRequest) -> HttpResult {
     let claim = get_info_in_token(&req)?;
@@ -235,6 +484,31 @@ async fn update_rank(
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
 
+#[get("/list_shop_items")]
+async fn list_shop_items(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let ret = shop_model::list_shop_items(&client, true).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// add an item when id is not set, otherwise update it
+#[post("/update_shop_item")]
+async fn update_shop_item(
+    data: web::Json<ShopItem>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let ret = shop_model::update_or_add_shop_item(&client, &data).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
 #[get("/list_site_settings")]
 async fn list_site_settings(req: HttpRequest) -> HttpResult {
     use std::collections::HashMap;
@@ -248,26 +522,14 @@ async fn list_site_settings(req: HttpRequest) -> HttpResult {
         let setting = KVDB.clone().get_string("config", key.as_ref())?.unwrap();
         settings.insert(key.to_string(), setting);
     }
-    let val = KVDB
-        .clone()
-        .get_float("config", "INVITE CONSUME".as_ref())?
-        .unwrap();
-    settings.insert("INVITE CONSUME".to_string(), val.to_string());
-    let val = KVDB
-        .clone()
-        .get_float("config", "BAN UPLOAD RATIO".as_ref())?
-        .unwrap();
-    settings.insert("BAN UPLOAD RATIO".to_string(), val.to_string());
-    let val = KVDB
-        .clone()
-        .get_number("config", "NEWBIE TERM".as_ref())?
-        .unwrap();
-    settings.insert("NEWBIE TERM".to_string(), val.to_string());
-    let val = KVDB
-        .clone()
-        .get_number("config", "LOGIN EXPIRE DAY".as_ref())?
-        .unwrap();
-    settings.insert("LOGIN EXPIRE DAY".to_string(), val.to_string());
+    for (key, _) in FLOAT_SITE_SETTING.iter() {
+        let setting = KVDB.clone().get_float("config", key.as_ref())?.unwrap();
+        settings.insert(key.to_string(), setting.to_string());
+    }
+    for (key, _) in NUMBER_SITE_SETTING.iter() {
+        let setting = KVDB.clone().get_number("config", key.as_ref())?.unwrap();
+        settings.insert(key.to_string(), setting.to_string());
+    }
 
     Ok(HttpResponse::Ok().json(settings.to_json()))
 }
@@ -281,32 +543,18 @@ async fn update_site_settings(data: web::Json<SiteSettingRequest>, req: HttpRequ
         return Err(Error::NoPermission);
     }
     for (key, val) in data.settings.iter() {
-        if key.eq("INVITE CONSUME") {
-            KVDB.clone().put(
-                "config",
-                "INVITE CONSUME".as_ref(),
-                &f64::from_str(&val).map_err(error_string)?.to_ne_bytes(),
-            )?;
-        }
-        if key.eq("BAN UPLOAD RATIO") {
-            KVDB.clone().put(
-                "config",
-                "BAN UPLOAD RATIO".as_ref(),
-                &f64::from_str(&val).map_err(error_string)?.to_ne_bytes(),
-            )?;
-        }
-        if key.eq("NEWBIE TERM") {
+        if FLOAT_SITE_SETTING.contains_key(key.as_str()) {
             KVDB.clone().put(
                 "config",
-                "NEWBIE TERM".as_ref(),
-                &i64::from_str(&val).map_err(error_string)?.to_ne_bytes(),
+                key.as_ref(),
+                &f64::from_str(val).map_err(error_string)?.to_ne_bytes(),
             )?;
         }
-        if key.eq("LOGIN EXPIRE DAY") {
+        if NUMBER_SITE_SETTING.contains_key(key.as_str()) {
             KVDB.clone().put(
                 "config",
-                "LOGIN EXPIRE DAY".as_ref(),
-                &i64::from_str(&val).map_err(error_string)?.to_ne_bytes(),
+                key.as_ref(),
+                &i64::from_str(val).map_err(error_string)?.to_ne_bytes(),
             )?;
         }
         if STRING_SITE_SETTING
@@ -321,6 +569,175 @@ async fn update_site_settings(data: web::Json<SiteSettingRequest>, req: HttpRequ
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
 
+#[get("/list_ip_ban")]
+async fn list_ip_ban(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let ret = ip_ban_model::list_ip_bans(&client).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// a single address is taken as a /32 or /128 network
+#[post("/add_ip_ban")]
+async fn add_ip_ban(
+    data: web::Json<IpBanRequest>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    use ipnet::IpNet;
+    use std::net::IpAddr;
+
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let cidr = data.cidr.trim();
+    let net = cidr
+        .parse::<IpNet>()
+        .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| Error::RequestError("invalid CIDR".to_string()))?
+        .trunc();
+    let ret = ip_ban_model::add_ip_ban(
+        &client,
+        &net.to_string(),
+        data.reason.as_deref().unwrap_or_default(),
+        &claim.sub,
+    )
+    .await?;
+    update_ip_ban(vec![ret.cidr.clone()], vec![]).await?;
+
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// Ban all ranges announced by an autonomous system, fetched from
+/// `ASN PREFIXES URL`. Banning it again follows changes of its ranges.
+#[post("/add_asn_ban")]
+async fn add_asn_ban(
+    data: web::Json<AsnBanRequest>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let url = KVDB
+        .clone()
+        .get_string("config", "ASN PREFIXES URL".as_ref())?
+        .unwrap_or_default()
+        .replace("{asn}", &data.asn.to_string());
+    let resp: serde_json::Value = reqwest::get(&url)
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| Error::OtherError(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| Error::OtherError(e.to_string()))?;
+    let cidrs = announced_prefixes(&resp);
+    if cidrs.is_empty() {
+        return Err(Error::RequestError(format!(
+            "no range announced by AS{}",
+            data.asn
+        )));
+    }
+    let reason = match data.reason.as_deref() {
+        Some(reason) => reason.to_string(),
+        None => format!("AS{}", data.asn),
+    };
+    let (set, deleted) =
+        ip_ban_model::replace_asn_bans(&client, data.asn, &cidrs, &reason, &claim.sub).await?;
+    update_ip_ban(
+        set.iter().map(|x| x.cidr.clone()).collect(),
+        deleted.into_iter().map(|x| x.cidr).collect(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(set.to_json()))
+}
+
+#[post("/delete_ip_ban")]
+async fn delete_ip_ban(
+    data: web::Json<IdsWrapper>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let ret = ip_ban_model::delete_ip_ban_by_ids(&client, &data.ids).await?;
+    update_ip_ban(vec![], ret.into_iter().map(|x| x.cidr).collect()).await?;
+
+    Ok(HttpResponse::Ok().json(GeneralResponse::default()))
+}
+
+/// daily totals of all users in recent days, 30 days by default
+#[get("/list_site_stats")]
+async fn list_site_stats(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let days = deserialize_from_req!(req, DaysWrapper)
+        .days
+        .unwrap_or(30)
+        .clamp(1, 365);
+    let ret = stat_model::find_site_stats(&client, days).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[get("/list_site_events")]
+async fn list_site_events(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let ret = site_event_model::list_site_events(&client).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[post("/add_site_event")]
+async fn add_site_event(
+    data: web::Json<SiteEventRequest>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    if data.start >= data.end {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("end before start")));
+    }
+    let ret = site_event_model::add_site_event(
+        &client,
+        &data.name,
+        data.promotion,
+        data.tags.as_deref(),
+        data.start,
+        data.end,
+        &claim.sub,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[post("/delete_site_events")]
+async fn delete_site_events(
+    data: web::Json<IdsWrapper>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_no_permission_to_site(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    site_event_model::delete_site_event_by_ids(&client, &data.ids).await?;
+    Ok(HttpResponse::Ok().json(GeneralResponse::default()))
+}
+
 pub(crate) fn admin_service() -> Scope {
     web::scope("/admin")
         .service(
@@ -328,9 +745,10 @@ pub(crate) fn admin_service() -> Scope {
                 .service(accept_torrents)
                 .service(stick_torrents)
                 .service(unstick_torrents)
-                .service(free_torrents)
-                .service(unfree_torrents)
-                .service(show_invisible_torrents),
+                .service(promote_torrents)
+                .service(show_invisible_torrents)
+                .service(list_hit_and_run_rules)
+                .service(update_hit_and_run_rule),
         )
         .service(
             web::scope("/user")
@@ -339,7 +757,13 @@ pub(crate) fn admin_service() -> Scope {
                 .service(list_banned_user)
                 .service(group_awards)
                 .service(change_permission)
-                .service(award_rank),
+                .service(award_rank)
+                .service(list_rank_logs)
+                .service(list_hit_and_runs)
+                .service(list_money_ledger)
+                .service(list_ratio_watches)
+                .service(pardon_hit_and_runs)
+                .service(punish_hit_and_runs),
         )
         .service(
             web::scope("/site")
@@ -347,7 +771,17 @@ pub(crate) fn admin_service() -> Scope {
                 .service(update_email_whitelist)
                 .service(get_rank)
                 .service(update_rank)
+                .service(list_shop_items)
+                .service(update_shop_item)
                 .service(list_site_settings)
-                .service(update_site_settings),
+                .service(update_site_settings)
+                .service(list_ip_ban)
+                .service(add_ip_ban)
+                .service(add_asn_ban)
+                .service(delete_ip_ban)
+                .service(list_site_events)
+                .service(add_site_event)
+                .service(delete_site_events)
+                .service(list_site_stats),
         )
 }
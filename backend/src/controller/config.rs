@@ -31,7 +31,47 @@ lazy_static! {
 lazy_static! {
     pub static ref STRING_SITE_SETTING: HashMap<&'static str, &'static str> = [
         ("SITE NAME", "SOPT"),
+        ("SITE URL", "https://sopt.rs"),
         ("ACTIVATE EMAIL", "Welcome to register SOPT!\n\nClick following address to activate: https://sopt.rs/auth/activate"),
         ("PASSWORD RESET EMAIL", "Code will be expired in 30 minutes.\n\nClick following address to reset your password: https://sopt.rs/auth/validate_reset"),
+        ("TORRENT SOURCE", ""),
+        ("ANNOUNCE LIST", ""),
+        ("WEB SEEDS", ""),
+        ("FORBIDDEN FILE EXTENSIONS", "lnk,scr,url,vbs"),
+        ("FORBIDDEN FILE NAMES", "desktop.ini,thumbs.db,.ds_store"),
+        ("ASN PREFIXES URL", "https://stat.ripe.net/data/announced-prefixes/data.json?resource=AS{asn}"),
+    ].iter().copied().collect();
+}
+
+lazy_static! {
+    /// float settings and their default values
+    pub static ref FLOAT_SITE_SETTING: HashMap<&'static str, f64> = [
+        ("INVITE CONSUME", 5000.0),
+        ("BAN UPLOAD RATIO", 0.3),
+        ("HNR MIN RATIO", 1.0),
+        ("HNR PENALTY", 10000.0),
+        ("BONUS T0", 4.0),
+        ("BONUS N0", 7.0),
+        ("BONUS B0", 100.0),
+        ("BONUS L", 300.0),
+        ("DUPE SIMILARITY", 0.8),
+    ].iter().copied().collect();
+}
+
+lazy_static! {
+    /// integer settings and their default values
+    pub static ref NUMBER_SITE_SETTING: HashMap<&'static str, i64> = [
+        ("NEWBIE TERM", 14),
+        ("LOGIN EXPIRE DAY", 3),
+        ("HNR MIN SEED HOURS", 72),
+        ("HNR GRACE DAYS", 14),
+        ("TOKEN DURATION HOURS", 24),
+        ("RATIO WATCH DAYS", 14),
+        ("TORRENT MAX FILES", 10000),
+        ("ZIP MAX TORRENTS", 100),
+        ("ZIP MAX BYTES", 10485760),
+        ("SUBSCRIPTION MAX", 20),
+        ("SUBSCRIPTION HOURLY LIMIT", 20),
+        ("SUBSCRIPTION DIGEST HOURS", 24),
     ].iter().copied().collect();
 }
@@ -0,0 +1,165 @@
+use super::*;
+use crate::data::{
+    promotion::Promotion, ratio_watch as ratio_watch_model, torrent as torrent_model,
+    torrent_info as torrent_info_model, user as user_model,
+};
+
+/// unknown feed token or passkey fails like a missing jwt
+fn not_found_as_auth_error(err: Error) -> Error {
+    match err {
+        Error::NotFound => Error::AuthError,
+        err => err,
+    }
+}
+
+#[get("/reset_token")]
+async fn reset_token(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    let username = claim.sub;
+    if is_not_ordinary_user(claim.role) {
+        return Err(Error::NoPermission);
+    }
+
+    let token = generate_passkey(&username)?;
+    user_model::update_feed_token_by_username(&client, &username, &token).await?;
+    Ok(HttpResponse::Ok().json(token.to_json()))
+}
+
+/// title and address of the site, used as channel of feeds
+fn get_site() -> Result<(String, String), Error> {
+    let kv = KVDB.clone();
+    let name = kv
+        .get_string("config", "SITE NAME".as_ref())?
+        .unwrap_or_default();
+    let url = kv
+        .get_string("config", "SITE URL".as_ref())?
+        .unwrap_or_default();
+    Ok((name, url.trim_end_matches('/').to_string()))
+}
+
+/// Items of the latest torrents matching the request, at most 100.
+/// Torrent files are downloaded with passkey of the feed token owner.
+async fn find_feed_items(
+    client: &sqlx::PgPool,
+    data: FeedRequest,
+    site_url: &str,
+) -> Result<Vec<FeedItem>, Error> {
+    let user = user_model::find_user_by_feed_token(client, &data.token)
+        .await
+        .map_err(not_found_as_auth_error)?;
+    if is_not_ordinary_user(user.role) {
+        return Err(Error::NoPermission);
+    }
+
+    let ids = match data.keywords {
+        Some(keywords) if !keywords.is_empty() => {
+            Some(TORRENT_SEARCH_ENGINE.read().await.search(keywords))
+        }
+        _ => None,
+    };
+    let mut torrents = torrent_info_model::find_feed_torrents(
+        client,
+        data.tags.as_deref().unwrap_or(&[]),
+        ids.as_deref(),
+        data.min_size,
+        data.max_size,
+        data.num.unwrap_or(50).min(100) as i64,
+    )
+    .await?;
+    if let Some(promotion) = data.promotion {
+        torrents.retain(|t| {
+            Promotion::active(t.promotion, t.promotionstart, t.promotionend) == promotion
+        });
+    }
+
+    Ok(torrents
+        .into_iter()
+        .map(|t| FeedItem {
+            title: t.title,
+            link: format!("{}/torrent/{}", site_url, t.id),
+            author: t.poster,
+            description: t.description,
+            categories: t.tag.unwrap_or_default(),
+            date: t.lastedit,
+            enclosure: format!(
+                "{}/api/feed/download?id={}&passkey={}",
+                site_url, t.id, user.passkey
+            ),
+            length: t.length,
+        })
+        .collect())
+}
+
+#[get("/rss")]
+async fn rss(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let data = deserialize_from_req!(req, FeedRequest);
+    let (name, url) = get_site()?;
+    let items = find_feed_items(&client, data, &url).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/rss+xml; charset=utf-8")
+        .body(generate_rss(&name, &url, &items)))
+}
+
+#[get("/atom")]
+async fn atom(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let data = deserialize_from_req!(req, FeedRequest);
+    let (name, url) = get_site()?;
+    let items = find_feed_items(&client, data, &url).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/atom+xml; charset=utf-8")
+        .body(generate_atom(&name, &url, &items)))
+}
+
+/// download torrents by passkey instead of jwt,
+/// permission is checked the same as `get_torrent`
+#[get("/download")]
+async fn download(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let data = deserialize_from_req!(req, FeedDownloadRequest);
+    let user = user_model::find_user_by_passkey(&client, &data.passkey)
+        .await
+        .map_err(not_found_as_auth_error)?;
+    if is_not_ordinary_user(user.role) {
+        return Err(Error::NoPermission);
+    }
+    if !can_download(&client, data.id, &user.username, user.role).await? {
+        return Err(Error::NoPermission);
+    }
+
+    if ratio_watch_model::find_ratio_watch_by_uid(&client, user.id)
+        .await?
+        .is_some()
+    {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(
+            "downloading is restricted under ratio watch",
+        )));
+    }
+
+    let torrent = torrent_model::find_torrent_by_id(&client, data.id).await?;
+    let meta = get_torrent_meta(torrent.comment)?;
+    let generated_torrent = generate_torrent_file(
+        torrent.info,
+        torrent.piecelayers,
+        &meta,
+        &user.passkey,
+        torrent.id,
+        user.id,
+    );
+
+    Ok(HttpResponse::Ok()
+        .append_header((
+            http::header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.torrent\"", torrent.name),
+        ))
+        .content_type("application/x-bittorrent")
+        .body(generated_torrent))
+}
+
+pub(crate) fn feed_service() -> Scope {
+    web::scope("/feed")
+        .service(reset_token)
+        .service(rss)
+        .service(atom)
+        .service(download)
+}
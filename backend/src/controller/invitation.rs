@@ -1,5 +1,9 @@
 use super::*;
-use crate::data::{invitation as invitation_model, user_info as user_info_model};
+use crate::data::{
+    invitation as invitation_model, ledger as ledger_model,
+    ledger::{LedgerReason, MoneyChange},
+    user_info as user_info_model,
+};
 
 #[post("/send_invitation")]
 async fn send_invitation(
@@ -14,13 +18,24 @@ async fn send_invitation(
     }
 
     let code = generate_random_code();
-    let num = KVDB
-        .clone()
-        .get_float("config", "INVITE CONSUME".as_ref())?
-        .unwrap();
-    user_info_model::update_money_by_name(&client, &username, num).await?;
+    // the cost and the code are committed together
+    let mut tx = client.begin().await?;
+    // invitation slots go first
+    if !user_info_model::consume_invite_by_name(&mut tx, &username).await? {
+        let num = KVDB
+            .clone()
+            .get_float("config", "INVITE CONSUME".as_ref())?
+            .unwrap();
+        let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+        ledger_model::record_money_change(
+            &mut tx,
+            MoneyChange::new(user.id, -num, LedgerReason::Invitation),
+        )
+        .await?;
+    }
     let ret =
-        invitation_model::add_invitation_code(&client, &username, &code, &data.address).await?;
+        invitation_model::add_invitation_code(&mut tx, &username, &code, &data.address).await?;
+    tx.commit().await?;
     // we don't really care about the result of send mail
     std::thread::spawn(move || {
         send_mail(
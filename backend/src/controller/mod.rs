@@ -1,16 +1,22 @@
 mod admin;
 mod config;
+mod feed;
 mod invitation;
 #[cfg(feature = "message")]
 mod message;
 mod request;
+mod shop;
+mod site;
+mod subscription;
 mod torrent;
 mod tracker;
 mod user;
 
 use crate::config::CONFIG;
 use crate::controller::config::*;
-pub use crate::controller::config::{ALLOWED_DOMAIN, STRING_SITE_SETTING};
+pub use crate::controller::config::{
+    ALLOWED_DOMAIN, FLOAT_SITE_SETTING, NUMBER_SITE_SETTING, STRING_SITE_SETTING,
+};
 use crate::data::kv::KVDB;
 use crate::data::*;
 use crate::deserialize_from_req;
@@ -60,6 +66,59 @@ fn get_name_in_token(req: &HttpRequest) -> Result<String, Error> {
     Ok(get_info_in_token(req)?.sub)
 }
 
+/// trackers and web seeds written into generated torrents
+///
+/// `ANNOUNCE LIST` holds tiers separated by `;` and addresses of a tier
+/// separated by `,`, `announce_addr` is always in the first tier.
+fn get_torrent_meta(comment: Option<String>) -> Result<TorrentMeta, Error> {
+    let setting = |key: &str| -> Result<String, Error> {
+        Ok(KVDB
+            .clone()
+            .get_string("config", key.as_ref())?
+            .unwrap_or_default())
+    };
+    let split = |s: &str, sep: char| -> Vec<String> {
+        s.split(sep)
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(String::from)
+            .collect()
+    };
+
+    let mut announce_list: Vec<Vec<String>> = setting("ANNOUNCE LIST")?
+        .split(';')
+        .map(|tier| split(tier, ','))
+        .filter(|tier| !tier.is_empty())
+        .collect();
+    match announce_list.first_mut() {
+        Some(tier) if tier.contains(&CONFIG.announce_addr) => (),
+        Some(tier) => tier.insert(0, CONFIG.announce_addr.clone()),
+        None => announce_list.push(vec![CONFIG.announce_addr.clone()]),
+    }
+
+    Ok(TorrentMeta {
+        announce_list,
+        web_seeds: split(&setting("WEB SEEDS")?, ','),
+        comment,
+        created_by: format!("SOPT {}", env!("CARGO_PKG_VERSION")),
+        creation_date: chrono::Utc::now().timestamp(),
+    })
+}
+
+/// invisible torrents can only be downloaded by the creator
+/// or user with torrent admin role
+async fn can_download(
+    client: &sqlx::PgPool,
+    tid: i64,
+    username: &str,
+    role: i64,
+) -> Result<bool, Error> {
+    let torrent_info = torrent_info::find_torrent_by_id_mini(client, tid).await?;
+    Ok(torrent_info.visible
+        || username.eq(&torrent_info.poster)
+        || !is_no_permission_to_torrents(role))
+}
+
 #[derive(Serialize, Debug)]
 struct UpdateFilter {
     set: Option<String>,
@@ -84,12 +143,74 @@ async fn update_passkey_filter(set: Option<String>, delete: Option<String>) -> R
     Ok(())
 }
 
+#[derive(Serialize, Debug)]
+struct UpdateIpBan {
+    set: Vec<String>,
+    delete: Vec<String>,
+}
+
+async fn update_ip_ban(set: Vec<String>, delete: Vec<String>) -> Result<(), Error> {
+    let addr = format!("http://{}/tracker/update_ip_ban", CONFIG.tracker_addr);
+    let client = reqwest::Client::new();
+    let query = UpdateIpBan { set, delete };
+
+    let resp = client
+        .post(&addr)
+        .json(&query)
+        .send()
+        .await
+        .map_err(|e| Error::OtherError(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(Error::OtherError("unable to set ip ban".to_string()));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct UpdateInfoHash<'a> {
+    tid: i64,
+    infohash: &'a str,
+    infohash_v2: Option<&'a str>,
+}
+
+/// tell the tracker infohashes of torrent `tid`, announces with others are refused
+async fn update_info_hash(
+    tid: i64,
+    infohash: &str,
+    infohash_v2: Option<&str>,
+) -> Result<(), Error> {
+    let addr = format!("http://{}/tracker/update_info_hash", CONFIG.tracker_addr);
+    let client = reqwest::Client::new();
+    let query = UpdateInfoHash {
+        tid,
+        infohash,
+        infohash_v2,
+    };
+
+    let resp = client
+        .post(&addr)
+        .json(&query)
+        .send()
+        .await
+        .map_err(|e| Error::OtherError(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(Error::OtherError("unable to set infohash".to_string()));
+    }
+
+    Ok(())
+}
+
 pub fn api_service() -> Scope {
     let mut scope = web::scope("/api")
         .service(user::user_service())
         .service(invitation::invitation_service())
         .service(torrent::torrent_service())
         .service(admin::admin_service())
+        .service(feed::feed_service())
+        .service(shop::shop_service())
+        .service(site::site_service())
+        .service(subscription::subscription_service())
         .service(tracker::tracker_service());
 
     #[cfg(feature = "message")]
@@ -1,4 +1,7 @@
 use super::*;
+use crate::data::promotion::Promotion;
+use crate::data::token::TokenKind;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 
 // single wrapper
@@ -23,6 +26,10 @@ pub struct NumWrapper {
     pub num: Option<usize>,
 }
 #[derive(Deserialize, Debug)]
+pub struct DaysWrapper {
+    pub days: Option<i32>,
+}
+#[derive(Deserialize, Debug)]
 pub struct PasswordWrapper {
     pub id: Option<i64>,
     pub code: Option<String>,
@@ -53,6 +60,11 @@ pub struct TransferRequest {
     pub amount: f64,
 }
 #[derive(Deserialize, Debug)]
+pub struct LedgerRequest {
+    pub id: Option<i64>,
+    pub page: Option<usize>,
+}
+#[derive(Deserialize, Debug)]
 pub struct ActivateRequest {
     pub id: i64,
     pub code: String,
@@ -79,6 +91,18 @@ pub struct MessageDeleteRequest {
     pub sender: bool,
 }
 
+// shop
+#[derive(Deserialize, Debug)]
+pub struct PurchaseRequest {
+    pub item: i64,
+    pub detail: Option<String>,
+}
+#[derive(Deserialize, Debug)]
+pub struct ApplyTokenRequest {
+    pub tid: i64,
+    pub kind: TokenKind,
+}
+
 // torrent
 #[derive(Deserialize, Debug)]
 pub enum Sort {
@@ -111,6 +135,11 @@ pub struct TorrentRequest {
     pub sort_type: Option<SortType>,
 }
 #[derive(Deserialize, Debug)]
+pub struct TorrentFilesRequest {
+    pub id: i64,
+    pub page: Option<usize>,
+}
+#[derive(Deserialize, Debug)]
 pub struct TorrentPostRequest {
     pub id: Option<i64>,
     pub title: String,
@@ -118,8 +147,46 @@ pub struct TorrentPostRequest {
     pub tags: Option<Vec<String>>,
 }
 
+// feed
+#[derive(Deserialize, Debug)]
+pub struct FeedRequest {
+    pub token: String,
+    pub tags: Option<Vec<String>>,
+    pub keywords: Option<Vec<String>>,
+    pub promotion: Option<Promotion>,
+    #[serde(rename = "minSize")]
+    pub min_size: Option<i64>,
+    #[serde(rename = "maxSize")]
+    pub max_size: Option<i64>,
+    pub num: Option<usize>,
+}
+#[derive(Deserialize, Debug)]
+pub struct FeedDownloadRequest {
+    pub id: i64,
+    pub passkey: String,
+}
+
+// subscription
+#[derive(Deserialize, Debug)]
+pub struct SubscriptionRequest {
+    pub name: String,
+    pub keywords: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+}
+#[derive(Deserialize, Debug)]
+pub struct DigestRequest {
+    pub digest: bool,
+}
+
 // admin
 #[derive(Deserialize, Debug)]
+pub struct PromotionRequest {
+    pub ids: Vec<i64>,
+    pub promotion: Promotion,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+#[derive(Deserialize, Debug)]
 pub struct GroupAwardRequest {
     pub ids: Vec<i64>,
     pub amount: f64,
@@ -128,6 +195,7 @@ pub struct GroupAwardRequest {
 pub struct RankAwardRequest {
     pub uid: i64,
     pub rid: i32,
+    pub locked: Option<bool>,
 }
 #[derive(Deserialize, Debug)]
 pub struct EmailListRequest {
@@ -144,3 +212,35 @@ pub struct PermissionRequest {
 pub struct SiteSettingRequest {
     pub settings: HashMap<String, String>,
 }
+#[derive(Deserialize, Debug)]
+pub struct HitAndRunListRequest {
+    pub status: Option<i32>,
+}
+#[derive(Deserialize, Debug)]
+pub struct HitAndRunRuleRequest {
+    pub tid: i64,
+    #[serde(rename = "minSeedHours")]
+    pub min_seed_hours: Option<i64>,
+    #[serde(rename = "minRatio")]
+    pub min_ratio: Option<f64>,
+    #[serde(rename = "graceDays")]
+    pub grace_days: Option<i64>,
+}
+#[derive(Deserialize, Debug)]
+pub struct SiteEventRequest {
+    pub name: String,
+    pub promotion: Promotion,
+    pub tags: Option<Vec<String>>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+#[derive(Deserialize, Debug)]
+pub struct IpBanRequest {
+    pub cidr: String,
+    pub reason: Option<String>,
+}
+#[derive(Deserialize, Debug)]
+pub struct AsnBanRequest {
+    pub asn: i64,
+    pub reason: Option<String>,
+}
@@ -0,0 +1,86 @@
+use super::*;
+use crate::data::{
+    shop as shop_model, token as token_model, torrent_info as torrent_info_model,
+    user_info as user_info_model,
+};
+
+#[get("/list_items")]
+async fn list_items(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    get_info_in_token(&req)?;
+    let ret = shop_model::list_shop_items(&client, false).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[post("/purchase")]
+async fn purchase(
+    data: web::Json<PurchaseRequest>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_not_ordinary_user(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let user = user_info_model::find_user_info_by_name_mini(&client, &claim.sub).await?;
+    let ret =
+        shop_model::purchase_item(&client, user.id, data.item, data.detail.as_deref()).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[get("/list_purchases")]
+async fn list_purchases(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let ret = shop_model::find_purchases_by_uid(&client, user.id).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[get("/list_tokens")]
+async fn list_tokens(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let ret = token_model::find_tokens_by_uid(&client, user.id).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// apply a token to a torrent for `TOKEN DURATION HOURS`
+#[post("/apply_token")]
+async fn apply_token(
+    data: web::Json<ApplyTokenRequest>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    if is_not_ordinary_user(claim.role) {
+        return Err(Error::NoPermission);
+    }
+    let torrent = torrent_info_model::find_torrent_by_id_mini(&client, data.tid).await?;
+    if !torrent.visible {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("torrent not visible")));
+    }
+    let hours = KVDB
+        .clone()
+        .get_number("config", "TOKEN DURATION HOURS".as_ref())?
+        .unwrap();
+    let user = user_info_model::find_user_info_by_name_mini(&client, &claim.sub).await?;
+    let ret = token_model::apply_token(&client, user.id, data.tid, data.kind, hours).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[get("/list_token_usages")]
+async fn list_token_usages(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let ret = token_model::find_token_usages_by_uid(&client, user.id).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+pub(crate) fn shop_service() -> Scope {
+    web::scope("/shop")
+        .service(list_items)
+        .service(purchase)
+        .service(list_purchases)
+        .service(list_tokens)
+        .service(apply_token)
+        .service(list_token_usages)
+}
@@ -0,0 +1,18 @@
+use super::*;
+use crate::data::site_event as site_event_model;
+
+/// public status of the site, no login needed
+#[get("/status")]
+async fn status(client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let name = KVDB
+        .clone()
+        .get_string("config", "SITE NAME".as_ref())?
+        .unwrap_or_default();
+    let events = site_event_model::find_active_events(&client).await?;
+    let ret = SiteStatus { name, events };
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+pub(crate) fn site_service() -> Scope {
+    web::scope("/site").service(status)
+}
@@ -0,0 +1,83 @@
+use super::*;
+use crate::data::{subscription as subscription_model, user as user_model};
+
+/// subscribe torrents by keywords and tags, at most `SUBSCRIPTION MAX` each user
+#[post("/add_subscription")]
+async fn add_subscription(
+    data: web::Json<SubscriptionRequest>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    let username = claim.sub;
+    if is_not_ordinary_user(claim.role) {
+        return Err(Error::NoPermission);
+    }
+
+    let keywords = data.keywords.as_deref().unwrap_or(&[]);
+    let tags = data.tags.as_deref().unwrap_or(&[]);
+    if keywords.is_empty() && tags.is_empty() {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("keywords or tags needed")));
+    }
+    if tags.len() > 5 {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("tags max amount is 5")));
+    }
+    let max = KVDB
+        .clone()
+        .get_number("config", "SUBSCRIPTION MAX".as_ref())?
+        .unwrap_or(20);
+    let user = user_model::find_user_by_username(&client, &username).await?;
+    if subscription_model::query_subscription_counts_by_uid(&client, user.id).await? >= max {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(&format!(
+            "subscriptions max amount is {}",
+            max
+        ))));
+    }
+
+    let ret =
+        subscription_model::add_subscription(&client, user.id, &data.name, keywords, tags).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[post("/delete_subscriptions")]
+async fn delete_subscriptions(
+    data: web::Json<IdsWrapper>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let user = user_model::find_user_by_username(&client, &username).await?;
+    subscription_model::delete_subscriptions(&client, &data.ids, user.id).await?;
+    Ok(HttpResponse::Ok().json(GeneralResponse::default()))
+}
+
+#[get("/list_subscriptions")]
+async fn list_subscriptions(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let user = user_model::find_user_by_username(&client, &username).await?;
+    let ret = SubscriptionList {
+        digest: subscription_model::find_digest_by_uid(&client, user.id).await?,
+        subscriptions: subscription_model::find_subscriptions_by_uid(&client, user.id).await?,
+    };
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+#[post("/set_digest")]
+async fn set_digest(
+    data: web::Json<DigestRequest>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let user = user_model::find_user_by_username(&client, &username).await?;
+    subscription_model::update_digest_by_uid(&client, user.id, data.digest).await?;
+    Ok(HttpResponse::Ok().json(GeneralResponse::default()))
+}
+
+pub(crate) fn subscription_service() -> Scope {
+    web::scope("/subscription")
+        .service(add_subscription)
+        .service(delete_subscriptions)
+        .service(list_subscriptions)
+        .service(set_digest)
+}
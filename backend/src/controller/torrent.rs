@@ -1,7 +1,7 @@
 use super::*;
 use crate::data::{
-    tag as tag_model, torrent as torrent_model, torrent_info as torrent_info_model,
-    user as user_model,
+    promotion::Promotion, ratio_watch as ratio_watch_model, tag as tag_model,
+    torrent as torrent_model, torrent_info as torrent_info_model, user as user_model,
 };
 
 #[post("/add_torrent")]
@@ -77,8 +77,9 @@ async fn update_torrent(
         for tag in to_decrease {
             tag_model::decrease_amount_by_name(&client, tag).await?;
         }
+        let mut conn = client.acquire().await?;
         for tag in to_increase {
-            tag_model::update_or_add_tag(&client, tag).await?;
+            tag_model::update_or_add_tag(&mut conn, tag).await?;
         }
     }
     Ok(HttpResponse::Ok().json(ret.to_json()))
@@ -123,7 +124,7 @@ async fn list_torrents(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> Htt
         .await?
     };
     if freeonly {
-        ret.retain(|t| t.free);
+        ret.retain(|t| Promotion::active(t.promotion, t.promotionstart, t.promotionend).is_free());
     }
 
     all_torrents.append(&mut ret);
@@ -160,7 +161,7 @@ async fn search_torrents(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> H
         .await?
     };
     if freeonly {
-        ret.retain(|t| t.free);
+        ret.retain(|t| Promotion::active(t.promotion, t.promotionstart, t.promotionend).is_free());
     }
 
     Ok(HttpResponse::Ok().json(ret.to_json()))
@@ -173,6 +174,61 @@ async fn list_posted_torrent(req: HttpRequest, client: web::Data<sqlx::PgPool>)
     Ok(HttpResponse::Ok().json(ret.to_json()))
 }
 
+/// upload policy in site settings, see `UploadPolicy`
+fn get_upload_policy() -> Result<UploadPolicy, Error> {
+    let kv = KVDB.clone();
+    let setting = |key: &str| -> Result<String, Error> {
+        Ok(kv.get_string("config", key.as_ref())?.unwrap_or_default())
+    };
+    let split = |s: String| -> Vec<String> {
+        s.split(',')
+            .map(|x| x.trim().to_lowercase())
+            .filter(|x| !x.is_empty())
+            .collect()
+    };
+
+    Ok(UploadPolicy {
+        // empty source means keeping what uploader sets
+        source: Some(setting("TORRENT SOURCE")?).filter(|source| !source.is_empty()),
+        forbidden_extensions: split(setting("FORBIDDEN FILE EXTENSIONS")?)
+            .into_iter()
+            .map(|ext| ext.trim_start_matches('.').to_string())
+            .collect(),
+        forbidden_names: split(setting("FORBIDDEN FILE NAMES")?),
+        max_files: kv
+            .get_number("config", "TORRENT MAX FILES".as_ref())?
+            .filter(|&max| max > 0)
+            .map(|max| max as usize),
+    })
+}
+
+/// the most similar torrent reaching `DUPE SIMILARITY`, see `torrent_similarity`
+async fn find_probable_dupe(
+    client: &sqlx::PgPool,
+    torrent: &torrent_model::TorrentTable,
+    id: i64,
+) -> Result<Option<(i64, f64)>, Error> {
+    use serde_bencode::from_bytes;
+
+    let threshold = KVDB
+        .clone()
+        .get_float("config", "DUPE SIMILARITY".as_ref())?
+        .unwrap_or(1.0);
+    let info = from_bytes::<torrent_model::Info>(&torrent.info).map_err(error_string)?;
+    let mut dupes = vec![];
+    for candidate in torrent_model::find_dupe_candidates(client, torrent.length, id).await? {
+        // broken ones are never duplicates
+        let Ok(other) = from_bytes::<torrent_model::Info>(&candidate.info) else {
+            continue;
+        };
+        let score = torrent_similarity(&info, &other);
+        if score >= threshold {
+            dupes.push((candidate.id, score));
+        }
+    }
+    Ok(dupes.into_iter().max_by(|x, y| x.1.total_cmp(&y.1)))
+}
+
 #[post("/upload_torrent")]
 async fn upload_torrent(
     mut payload: actix_multipart::Multipart,
@@ -187,6 +243,7 @@ async fn upload_torrent(
     let username = claim.sub;
     let mut parsed = None;
     let mut hash_map = HashMap::new();
+    let policy = get_upload_policy()?;
 
     while let Ok(Some(mut file)) = payload.try_next().await {
         let content_type = file.content_disposition().clone();
@@ -199,7 +256,7 @@ async fn upload_torrent(
             buf.append(&mut data.to_vec());
         }
         if name.is_empty() {
-            parsed = Some(parse_torrent_file(&buf)?);
+            parsed = Some(parse_torrent_file(&buf, &policy)?);
         } else {
             hash_map.insert(name.to_string(), String::from_utf8(buf).unwrap());
         }
@@ -221,7 +278,25 @@ async fn upload_torrent(
         return Err(Error::NoPermission);
     }
 
-    torrent_model::update_or_add_torrent(&client, &parsed.unwrap(), id).await?;
+    let parsed = parsed.unwrap();
+    if let Some(dupe) = torrent_model::find_id_by_infohash(&client, &parsed, id).await? {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(&format!(
+            "torrent already uploaded as {}",
+            dupe
+        ))));
+    }
+    let dupe = find_probable_dupe(&client, &parsed, id).await?;
+    match torrent_model::update_or_add_torrent(&client, &parsed, id).await {
+        // a concurrent upload of the same torrent came first
+        Err(Error::DBError(sqlx::Error::Database(e))) if e.is_unique_violation() => {
+            return Ok(
+                HttpResponse::Ok().json(GeneralResponse::from_err("torrent already uploaded"))
+            );
+        }
+        ret => ret?,
+    }
+    update_info_hash(id, &parsed.infohash, parsed.infohashv2.as_deref()).await?;
+    torrent_info_model::update_dupe_by_id(&client, id, dupe).await?;
 
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
@@ -241,6 +316,53 @@ async fn show_torrent(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> Http
     Ok(HttpResponse::Ok().json(ret.to_json()))
 }
 
+/// files of a torrent as a directory tree, 500 files each page
+#[get("/show_torrent_files")]
+async fn show_torrent_files(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let data = deserialize_from_req!(req, TorrentFilesRequest);
+    let torrent_info = torrent_info_model::find_torrent_by_id_mini(&client, data.id).await?;
+    if !torrent_info.visible {
+        let claim = get_info_in_token(&req)?;
+        if !torrent_info.poster.eq(&claim.sub) && is_no_permission_to_torrents(claim.role) {
+            return Err(Error::NoPermission);
+        }
+    }
+
+    let torrent = torrent_model::find_torrent_by_id(&client, data.id).await?;
+    let count = torrent.files.len();
+    // pages past the last one are clamped, also keeps the range from overflowing
+    let page = data.page.unwrap_or(0).min(count / 500);
+    // a single file torrent has no file list
+    let ret = if count == 0 {
+        FileNode {
+            name: torrent.name,
+            size: torrent.length,
+            count: 1,
+            children: None,
+        }
+    } else {
+        // sizes are not stored for torrents uploaded before, read them from info
+        let sizes = if torrent.filesizes.len() == count {
+            torrent.filesizes
+        } else {
+            serde_bencode::from_bytes::<torrent_model::Info>(&torrent.info)
+                .map_err(error_string)?
+                .file_list()
+                .into_iter()
+                .map(|(_, length)| length)
+                .collect()
+        };
+        build_file_tree(
+            &torrent.name,
+            &torrent.files,
+            &sizes,
+            page * 500..(page + 1) * 500,
+        )
+    };
+    let resp = DataWithCount::new(serde_json::to_value(ret).unwrap(), count as i64 / 500 + 1);
+    Ok(HttpResponse::Ok().json(resp.to_json()))
+}
+
 #[get("/get_torrent")]
 async fn get_torrent(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
     let claim = get_info_in_token(&req)?;
@@ -251,21 +373,28 @@ async fn get_torrent(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpR
 
     let data = deserialize_from_req!(req, IdWrapper);
     let user = user_model::find_user_by_username(&client, &username).await?;
-    let torrent_info = torrent_info_model::find_torrent_by_id_mini(&client, data.id).await?;
-    if !torrent_info.visible
-        && !username.eq(&torrent_info.poster)
-        && is_no_permission_to_torrents(claim.role)
-    {
+    if !can_download(&client, data.id, &username, claim.role).await? {
         return Err(Error::NoPermission);
     }
 
+    if ratio_watch_model::find_ratio_watch_by_uid(&client, user.id)
+        .await?
+        .is_some()
+    {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(
+            "downloading is restricted under ratio watch",
+        )));
+    }
+
     let torrent = torrent_model::find_torrent_by_id(&client, data.id).await?;
+    let meta = get_torrent_meta(torrent.comment)?;
     let generated_torrent = generate_torrent_file(
         torrent.info,
+        torrent.piecelayers,
+        &meta,
         &user.passkey,
         torrent.id,
         user.id,
-        &torrent.comment.unwrap_or_default(),
     );
 
     Ok(HttpResponse::Ok()
@@ -277,6 +406,158 @@ async fn get_torrent(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpR
         .body(generated_torrent))
 }
 
+#[get("/get_magnet")]
+async fn get_magnet(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let claim = get_info_in_token(&req)?;
+    let username = claim.sub;
+    if is_not_ordinary_user(claim.role) {
+        return Err(Error::NoPermission);
+    }
+
+    let data = deserialize_from_req!(req, IdWrapper);
+    let user = user_model::find_user_by_username(&client, &username).await?;
+    if !can_download(&client, data.id, &username, claim.role).await? {
+        return Err(Error::NoPermission);
+    }
+
+    if ratio_watch_model::find_ratio_watch_by_uid(&client, user.id)
+        .await?
+        .is_some()
+    {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(
+            "downloading is restricted under ratio watch",
+        )));
+    }
+
+    let torrent = torrent_model::find_torrent_by_id(&client, data.id).await?;
+    let meta = get_torrent_meta(None)?;
+    let ret = generate_magnet(&torrent, &meta, &user.passkey, user.id);
+
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// download personal torrent files of `ids` in a ZIP archive,
+/// limited by `ZIP MAX TORRENTS` and `ZIP MAX BYTES`.
+/// Permission and limits are checked before sending, then torrent
+/// files are generated and streamed one by one.
+#[post("/get_torrents")]
+async fn get_torrents(
+    mut data: web::Json<IdsWrapper>,
+    req: HttpRequest,
+    client: web::Data<sqlx::PgPool>,
+) -> HttpResult {
+    use futures::StreamExt;
+
+    let claim = get_info_in_token(&req)?;
+    let username = claim.sub;
+    if is_not_ordinary_user(claim.role) {
+        return Err(Error::NoPermission);
+    }
+
+    // a torrent appears once in the archive
+    data.ids.sort_unstable();
+    data.ids.dedup();
+
+    let kv = KVDB.clone();
+    let max_torrents = kv
+        .get_number("config", "ZIP MAX TORRENTS".as_ref())?
+        .unwrap_or(100);
+    let max_bytes = kv
+        .get_number("config", "ZIP MAX BYTES".as_ref())?
+        .unwrap_or(10485760);
+    if data.ids.is_empty() || data.ids.len() as i64 > max_torrents {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(&format!(
+            "1 to {} torrents can be downloaded at once",
+            max_torrents
+        ))));
+    }
+
+    let user = user_model::find_user_by_username(&client, &username).await?;
+    if ratio_watch_model::find_ratio_watch_by_uid(&client, user.id)
+        .await?
+        .is_some()
+    {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(
+            "downloading is restricted under ratio watch",
+        )));
+    }
+
+    for &id in &data.ids {
+        if !can_download(&client, id, &username, claim.role).await? {
+            return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(&format!(
+                "no permission to download torrent {}",
+                id
+            ))));
+        }
+    }
+    let sizes = torrent_model::find_stored_sizes_by_ids(&client, &data.ids).await?;
+    if sizes.len() != data.ids.len() {
+        return Err(Error::NotFound);
+    }
+    // torrent files are a little larger for announce addresses and so on
+    if sizes.iter().map(|(_, size)| size).sum::<i64>() > max_bytes {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err(&format!(
+            "torrents exceed the size limit of {} bytes",
+            max_bytes
+        ))));
+    }
+
+    let client = client.get_ref().clone();
+    let ids = std::mem::take(&mut data.ids).into_iter();
+    let body = futures::stream::unfold((ids, Some(ZipStream::new())), move |(mut ids, zip)| {
+        let client = client.clone();
+        let passkey = user.passkey.clone();
+        async move {
+            let mut zip = zip?;
+            let ret = match ids.next() {
+                Some(id) => personal_torrent_file(&client, id, &passkey, user.id)
+                    .await
+                    .and_then(|(name, content)| zip.add(&name, &content)),
+                None => return Some((zip.finish(), (ids, None))),
+            };
+            // the archive is broken once a file fails, so stop there
+            let zip = ret.is_ok().then_some(zip);
+            Some((ret, (ids, zip)))
+        }
+    })
+    .map(|ret| {
+        ret.map(web::Bytes::from).map_err(|e| {
+            log::error!("streaming torrents failed: {:?}", e);
+            std::io::Error::other("streaming torrents failed")
+        })
+    });
+
+    Ok(HttpResponse::Ok()
+        .append_header((
+            http::header::CONTENT_DISPOSITION,
+            "attachment; filename=\"torrents.zip\"",
+        ))
+        .content_type("application/zip")
+        .streaming(body))
+}
+
+/// name and personal torrent file of torrent `id` in archives
+async fn personal_torrent_file(
+    client: &sqlx::PgPool,
+    id: i64,
+    passkey: &str,
+    uid: i64,
+) -> Result<(String, Vec<u8>), Error> {
+    let torrent = torrent_model::find_torrent_by_id(client, id).await?;
+    let meta = get_torrent_meta(torrent.comment)?;
+    let content = generate_torrent_file(
+        torrent.info,
+        torrent.piecelayers,
+        &meta,
+        passkey,
+        torrent.id,
+        uid,
+    );
+    // id keeps names unique
+    let name = sanitize_filename::sanitize(format!("[{}] {}.torrent", id, torrent.name));
+    Ok((name, content))
+}
+
 pub(crate) fn torrent_service() -> Scope {
     web::scope("/torrent")
         .service(add_torrent)
@@ -285,7 +566,10 @@ pub(crate) fn torrent_service() -> Scope {
         .service(list_torrents)
         .service(search_torrents)
         .service(show_torrent)
+        .service(show_torrent_files)
         .service(list_posted_torrent)
         .service(upload_torrent)
         .service(get_torrent)
+        .service(get_magnet)
+        .service(get_torrents)
 }
@@ -1,6 +1,7 @@
 use super::*;
 use crate::data::{
-    torrent_info as torrent_info_model, torrent_status as torrent_status_model, user as user_model,
+    promotion::Promotion, site_event as site_event_model, token as token_model, token::TokenKind,
+    torrent_info as torrent_info_model, torrent_status as torrent_status_model,
     user_info as user_info_model,
 };
 
@@ -18,46 +19,37 @@ struct AnnouncePacket {
     tid: i64,
     download: i64,
     upload: i64,
+    left: Option<i64>,
     action: Option<Action>,
+    // when the tracker received it, missing in notifications of old trackers
+    time: Option<i64>,
 }
 
 #[get("/get_announce")]
 async fn get_announce(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
-    use chrono::{Duration, Utc};
-
-    let mut data = deserialize_from_req!(req, AnnouncePacket);
+    let data = deserialize_from_req!(req, AnnouncePacket);
     let torrent = torrent_info_model::find_torrent_by_id_mini(&client, data.tid).await?;
-    if torrent.free {
-        data.download = 0;
-    }
-    let current_status =
-        torrent_status_model::find_status_by_tid_uid(&client, data.tid, data.uid).await?;
-    if !current_status.is_empty() {
-        let status = current_status.first().unwrap();
-        if status.status < 2 {
-            user_info_model::update_money_by_id(
-                &client,
-                data.uid,
-                0.4 * torrent.length as f64 / (1024_i64 ^ 3_i64) as f64,
-            )
-            .await?;
+    // the most favorable of promotion, site events and personal tokens is credited
+    let mut promotions = site_event_model::find_active_promotions_by_tag(
+        &client,
+        torrent.tag.as_deref().unwrap_or_default(),
+    )
+    .await?;
+    promotions.push(Promotion::active(
+        torrent.promotion,
+        torrent.promotionstart,
+        torrent.promotionend,
+    ));
+    let (mut upload_factor, mut download_factor) = Promotion::most_favorable(promotions);
+    for kind in token_model::find_active_tokens(&client, data.uid, data.tid).await? {
+        match kind {
+            TokenKind::Freeleech => download_factor = 0.0,
+            TokenKind::DoubleUpload => upload_factor = upload_factor.max(2.0),
         }
     }
-    let ret =
-        user_info_model::update_io_by_id(&client, data.uid, data.upload, data.download).await?;
-    let ratio = KVDB
-        .clone()
-        .get_float("config", "BAN USER RATIO".as_ref())?
-        .unwrap();
-    let days = KVDB
-        .clone()
-        .get_number("config", "NEWBIE TERM".as_ref())?
-        .unwrap();
-    if (ret.upload as f64 / ret.download as f64) < ratio
-        && (Utc::now() - Duration::days(days)).timestamp() > ret.registertime.timestamp()
-    {
-        user_model::delete_role_by_id(&client, data.uid, 0).await?;
-    }
+    let upload = (data.upload as f64 * upload_factor) as i64;
+    let download = (data.download as f64 * download_factor) as i64;
+    user_info_model::update_io_by_id(&client, data.uid, upload, download).await?;
 
     let status = data.action.clone().unwrap_or(Action::Start) as i32;
     torrent_status_model::update_or_add_status(
@@ -67,6 +59,8 @@ async fn get_announce(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> Http
         status,
         data.upload,
         data.download,
+        data.left.map(|left| left == 0),
+        data.time,
     )
     .await?;
     if data.action.is_some() {
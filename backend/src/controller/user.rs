@@ -1,7 +1,10 @@
 use super::*;
 use crate::data::{
-    activation as activation_model, invitation as invitation_model, rank as rank_model,
-    torrent_status as torrent_status_model, user as user_model, user_info as user_info_model,
+    activation as activation_model, bonus as bonus_model, hit_and_run as hit_and_run_model,
+    invitation as invitation_model, ledger as ledger_model,
+    ledger::{LedgerReason, MoneyChange},
+    ratio_watch as ratio_watch_model, stat as stat_model, torrent_status as torrent_status_model,
+    user as user_model, user_info as user_info_model,
 };
 
 static ALLOWED_AVATAR_EXTENSION: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
@@ -90,7 +93,7 @@ async fn login(data: web::Json<LoginRequest>, client: web::Data<sqlx::PgPool>) -
     if validation.is_none() {
         return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("password not match")));
     }
-    let mut val = validation.unwrap();
+    let val = validation.unwrap();
     if !verify_password(&data.password, &val.password)? {
         return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("password not match")));
     }
@@ -100,24 +103,6 @@ async fn login(data: web::Json<LoginRequest>, client: web::Data<sqlx::PgPool>) -
     }
 
     user_info_model::update_activity_by_name(&client, &data.username).await?;
-    let current_rank = rank_model::find_rank_by_username(&client, &data.username).await?;
-    if current_rank.next.is_some() {
-        let next_rank = rank_model::find_rank_by_id(&client, current_rank.next.unwrap()).await?;
-        let info = user_info_model::find_user_info_by_name_mini(&client, &data.username).await?;
-        let current = Utc::now().timestamp();
-        let before = info.registertime.timestamp();
-        if info.upload > next_rank.upload && current - before > next_rank.age {
-            let roles = next_rank.role;
-            for role in roles {
-                user_model::add_role_by_id(&client, val.id, (role % 32) as i32).await?;
-            }
-            user_info_model::update_rank_by_name(&client, &data.username, next_rank.id).await?;
-            val = user_model::find_validation_by_name(&client, &data.username)
-                .await?
-                .pop()
-                .unwrap();
-        }
-    }
     let days = KVDB
         .clone()
         .get_number("config", "LOGIN EXPIRE DAY".as_ref())?
@@ -231,6 +216,78 @@ async fn show_torrent_status(req: HttpRequest, client: web::Data<sqlx::PgPool>)
     Ok(HttpResponse::Ok().json(ret.to_json()))
 }
 
+#[get("/list_hit_and_runs")]
+async fn list_hit_and_runs(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let ret = hit_and_run_model::find_hit_and_runs_by_uid(&client, user.id).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// daily stats of current user in recent days, 30 days by default
+#[get("/list_stats")]
+async fn list_stats(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let days = deserialize_from_req!(req, DaysWrapper)
+        .days
+        .unwrap_or(30)
+        .clamp(1, 365);
+    let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let ret = stat_model::find_user_stats(&client, user.id, days).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// ratio watch of current user, `None` if not watched
+#[get("/show_ratio_watch")]
+async fn show_ratio_watch(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let ret = ratio_watch_model::find_ratio_watch_by_uid(&client, user.id).await?;
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
+/// every change of money of current user, latest first
+#[get("/list_money_ledger")]
+async fn list_money_ledger(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    let username = get_name_in_token(&req)?;
+    let page = deserialize_from_req!(req, LedgerRequest).page.unwrap_or(0);
+    let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let count = ledger_model::query_ledger_counts_by_uid(&client, user.id).await?;
+    let ret = ledger_model::find_ledger_by_uid(&client, user.id, (page * 20) as i64).await?;
+    let resp = DataWithCount::new(serde_json::to_value(ret).unwrap(), count / 20 + 1);
+    Ok(HttpResponse::Ok().json(resp.to_json()))
+}
+
+/// show what current user would get in an hour if keeps seeding
+#[get("/preview_bonus")]
+async fn preview_bonus(req: HttpRequest, client: web::Data<sqlx::PgPool>) -> HttpResult {
+    use crate::job::bonus::BonusFormula;
+    use chrono::{Duration, Utc};
+
+    let username = get_name_in_token(&req)?;
+    let user = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let formula = BonusFormula::from_settings()?;
+    let now = Utc::now();
+    let torrents: Vec<BonusTorrent> =
+        bonus_model::find_seeding_torrents(&client, Some(user.id), now - Duration::hours(1))
+            .await?
+            .into_iter()
+            .map(|t| BonusTorrent {
+                weight: formula.torrent_weight(&t, now),
+                tid: t.tid,
+                title: t.title,
+            })
+            .collect();
+    let weight: f64 = torrents.iter().map(|t| t.weight).sum();
+    let ret = BonusPreview {
+        torrents,
+        weight,
+        hourly: formula.hourly(weight),
+    };
+
+    Ok(HttpResponse::Ok().json(ret.to_json()))
+}
+
 #[post("/reset_password")]
 async fn reset_password(
     data: web::Json<PasswordWrapper>,
@@ -268,7 +325,19 @@ async fn transfer_money(
         return Err(Error::NoPermission);
     }
 
-    user_info_model::transfer_money_by_name(&client, &username, &data.to, data.amount).await?;
+    if data.amount <= 0.0 || username == data.to {
+        return Ok(HttpResponse::Ok().json(GeneralResponse::from_err("invalid transfer")));
+    }
+    let from = user_info_model::find_user_info_by_name_mini(&client, &username).await?;
+    let to = user_info_model::find_user_info_by_name_mini(&client, &data.to).await?;
+    ledger_model::change_money(
+        &client,
+        vec![
+            MoneyChange::new(from.id, -data.amount, LedgerReason::Transfer).counterparty(&data.to),
+            MoneyChange::new(to.id, data.amount, LedgerReason::Transfer).counterparty(&username),
+        ],
+    )
+    .await?;
     Ok(HttpResponse::Ok().json(GeneralResponse::default()))
 }
 
@@ -389,6 +458,11 @@ pub(crate) fn user_service() -> Scope {
         .service(upload_avatar)
         .service(show_user)
         .service(show_torrent_status)
+        .service(list_hit_and_runs)
+        .service(preview_bonus)
+        .service(list_money_ledger)
+        .service(show_ratio_watch)
+        .service(list_stats)
         .service(
             web::scope("/auth")
                 .service(reset_password)
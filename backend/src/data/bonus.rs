@@ -0,0 +1,80 @@
+use super::ledger::{self, LedgerReason, MoneyChange};
+use super::*;
+
+/// Torrents seeded by anyone (or a definite user) since a definite time,
+/// with the number of seeders of each torrent.
+pub async fn find_seeding_torrents(
+    client: &sqlx::PgPool,
+    uid: Option<i64>,
+    since: DateTime<Utc>,
+) -> SeedingTorrentVecRet {
+    Ok(sqlx::query_as!(
+        SeedingTorrent,
+        "SELECT torrent_status.uid, torrent_status.tid, torrent_info.title, torrent.length, \
+        torrent_info.createTime, \
+        (SELECT COUNT(*) FROM torrent_status AS s \
+            WHERE s.tid = torrent_status.tid AND s.seeding AND s.status <> 2 \
+            AND s.lastannounce > $2) AS seeders \
+        FROM torrent_status INNER JOIN torrent ON torrent_status.tid = torrent.id \
+        INNER JOIN torrent_info ON torrent_status.tid = torrent_info.id \
+        WHERE torrent_status.seeding AND torrent_status.status <> 2 \
+        AND torrent_status.lastannounce > $2 \
+        AND ($1::BIGINT IS NULL OR torrent_status.uid = $1);",
+        uid,
+        since
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// Record the run of job `name`, unless it has run within `period` seconds.
+/// Concurrent callers wait for each other, so only one of them records it.
+async fn record_job_run(
+    tx: &mut sqlx::PgConnection,
+    name: &str,
+    period: f64,
+) -> Result<bool, Error> {
+    Ok(sqlx::query!(
+        "INSERT INTO job_run(name, lastRun) VALUES($1, now()) \
+        ON CONFLICT (name) DO UPDATE SET lastRun = now() \
+        WHERE job_run.lastRun <= now() - make_interval(secs => $2) \
+        RETURNING name;",
+        name,
+        period
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .is_some())
+}
+
+/// Record awards of (uid, amount, torrents, weight) and add money in one
+/// transaction, along with the run of the job. Returns false without
+/// awarding anything if it has been awarded within `period` seconds,
+/// by an earlier run or by another instance.
+pub async fn award_bonuses(
+    client: &sqlx::PgPool,
+    awards: &[(i64, f64, i32, f64)],
+    period: f64,
+) -> Result<bool, Error> {
+    let mut tx = client.begin().await?;
+    if !record_job_run(&mut tx, "bonus", period).await? {
+        return Ok(false);
+    }
+    for &(uid, amount, torrents, weight) in awards {
+        sqlx::query!(
+            "INSERT INTO bonus_award(uid, amount, torrents, weight) \
+            VALUES($1, $2, $3, $4);",
+            uid,
+            amount,
+            torrents,
+            weight
+        )
+        .execute(&mut *tx)
+        .await?;
+        ledger::record_money_change(&mut tx, MoneyChange::new(uid, amount, LedgerReason::Bonus))
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(true)
+}
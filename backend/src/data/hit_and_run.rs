@@ -0,0 +1,260 @@
+use super::*;
+
+/// status of a hit and run record
+#[repr(C)]
+#[derive(Deserialize, Debug, Copy, Clone)]
+pub enum HitAndRunStatus {
+    Marked = 0,
+    Pardoned,
+    Punished,
+}
+
+/// Minimums a finished download has to reach, by seeding time in seconds
+/// or by upload to size ratio, once its grace period is over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rule {
+    pub min_seedtime: i64,
+    pub min_ratio: f64,
+    pub grace_days: i64,
+}
+
+impl Rule {
+    /// rule of a torrent, those unset in `rule` fall back to `self`
+    pub fn overridden_by(&self, rule: &HitAndRunRule) -> Rule {
+        Rule {
+            min_seedtime: rule.minseedhours.map_or(self.min_seedtime, |h| h * 3600),
+            min_ratio: rule.minratio.unwrap_or(self.min_ratio),
+            grace_days: rule.gracedays.unwrap_or(self.grace_days),
+        }
+    }
+
+    fn unzip(rules: &[(i64, Rule)]) -> (Vec<i64>, Vec<i64>, Vec<f64>, Vec<i64>) {
+        let mut ret = (vec![], vec![], vec![], vec![]);
+        for (tid, rule) in rules {
+            ret.0.push(*tid);
+            ret.1.push(rule.min_seedtime);
+            ret.2.push(rule.min_ratio);
+            ret.3.push(rule.grace_days);
+        }
+        ret
+    }
+}
+
+/// Mark finished downloads which are neither seeded long enough
+/// nor uploaded enough after grace period, by rule of the torrent
+/// in `rules` or else `default`.
+/// Returns the number of new records.
+pub async fn mark_hit_and_runs(
+    client: &sqlx::PgPool,
+    now: DateTime<Utc>,
+    default: &Rule,
+    rules: &[(i64, Rule)],
+) -> Result<u64, Error> {
+    let (tids, seedtimes, ratios, grace_days) = Rule::unzip(rules);
+    let ret = sqlx::query!(
+        "WITH rule AS (\
+            SELECT * FROM UNNEST($1::BIGINT[], $2::BIGINT[], $3::DOUBLE PRECISION[], $4::BIGINT[]) \
+            AS r(tid, minSeedTime, minRatio, graceDays)\
+        ) INSERT INTO hit_and_run(tid, uid, seedtime, upload) \
+        SELECT torrent_status.tid, torrent_status.uid, torrent_status.seedtime, torrent_status.upload \
+        FROM torrent_status INNER JOIN torrent ON torrent_status.tid = torrent.id \
+        LEFT JOIN rule ON torrent_status.tid = rule.tid \
+        WHERE torrent_status.finished = TRUE \
+        AND torrent_status.finishtime + make_interval(days => COALESCE(rule.graceDays, $5)::INT) < $8 \
+        AND torrent_status.seedtime < COALESCE(rule.minSeedTime, $6) \
+        AND torrent_status.upload < COALESCE(rule.minRatio, $7) * torrent.length \
+        ON CONFLICT (tid, uid) DO NOTHING;",
+        &tids,
+        &seedtimes,
+        &ratios,
+        &grace_days,
+        default.grace_days,
+        default.min_seedtime,
+        default.min_ratio,
+        now
+    )
+    .execute(client)
+    .await?;
+
+    Ok(ret.rows_affected())
+}
+
+/// Remove marked records whose users have made up for it,
+/// by rule of the torrent in `rules` or else `default`.
+pub async fn clear_hit_and_runs(
+    client: &sqlx::PgPool,
+    default: &Rule,
+    rules: &[(i64, Rule)],
+) -> Result<u64, Error> {
+    let (tids, seedtimes, ratios, _) = Rule::unzip(rules);
+    let ret = sqlx::query!(
+        "WITH rule AS (\
+            SELECT * FROM UNNEST($1::BIGINT[], $2::BIGINT[], $3::DOUBLE PRECISION[]) \
+            AS r(tid, minSeedTime, minRatio)\
+        ) DELETE FROM hit_and_run USING torrent_status \
+        INNER JOIN torrent ON torrent.id = torrent_status.tid \
+        LEFT JOIN rule ON torrent_status.tid = rule.tid \
+        WHERE hit_and_run.status = 0 \
+        AND torrent_status.tid = hit_and_run.tid AND torrent_status.uid = hit_and_run.uid \
+        AND (torrent_status.seedtime >= COALESCE(rule.minSeedTime, $4) \
+        OR torrent_status.upload >= COALESCE(rule.minRatio, $5) * torrent.length);",
+        &tids,
+        &seedtimes,
+        &ratios,
+        default.min_seedtime,
+        default.min_ratio
+    )
+    .execute(client)
+    .await?;
+
+    Ok(ret.rows_affected())
+}
+
+pub async fn list_hit_and_run_rules(client: &sqlx::PgPool) -> HitAndRunRuleVecRet {
+    Ok(sqlx::query_as!(
+        HitAndRunRule,
+        "SELECT tid, minSeedHours, minRatio, graceDays FROM hit_and_run_rule ORDER BY tid;"
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+pub async fn update_or_add_hit_and_run_rule(
+    client: &sqlx::PgPool,
+    tid: i64,
+    min_seed_hours: Option<i64>,
+    min_ratio: Option<f64>,
+    grace_days: Option<i64>,
+) -> HitAndRunRuleRet {
+    Ok(sqlx::query_as!(
+        HitAndRunRule,
+        "INSERT INTO hit_and_run_rule(tid, minSeedHours, minRatio, graceDays) \
+        VALUES($1, $2, $3, $4) ON CONFLICT (tid) DO \
+        UPDATE SET minSeedHours = $2, minRatio = $3, graceDays = $4 \
+        RETURNING tid, minSeedHours, minRatio, graceDays;",
+        tid,
+        min_seed_hours,
+        min_ratio,
+        grace_days
+    )
+    .fetch_one(client)
+    .await?)
+}
+
+pub async fn delete_hit_and_run_rule(client: &sqlx::PgPool, tid: i64) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM hit_and_run_rule WHERE tid = $1;", tid)
+        .execute(client)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn find_hit_and_runs_by_uid(client: &sqlx::PgPool, uid: i64) -> HitAndRunVecRet {
+    Ok(sqlx::query_as!(
+        HitAndRun,
+        "SELECT hit_and_run.id, tid, torrent_info.title, uid, username, seedtime, hit_and_run.upload, \
+        status, operator, hit_and_run.createTime \
+        FROM hit_and_run INNER JOIN torrent_info ON hit_and_run.tid = torrent_info.id \
+        INNER JOIN user_info ON hit_and_run.uid = user_info.id \
+        WHERE uid = $1 ORDER BY hit_and_run.id DESC;",
+        uid
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+pub async fn find_hit_and_runs_by_status(client: &sqlx::PgPool, status: i32) -> HitAndRunVecRet {
+    Ok(sqlx::query_as!(
+        HitAndRun,
+        "SELECT hit_and_run.id, tid, torrent_info.title, uid, username, seedtime, hit_and_run.upload, \
+        status, operator, hit_and_run.createTime \
+        FROM hit_and_run INNER JOIN torrent_info ON hit_and_run.tid = torrent_info.id \
+        INNER JOIN user_info ON hit_and_run.uid = user_info.id \
+        WHERE status = $1 ORDER BY hit_and_run.id DESC;",
+        status
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// Settle marked records, returns uid of every settled record.
+pub async fn settle_hit_and_runs(
+    client: &sqlx::PgPool,
+    ids: &[i64],
+    status: HitAndRunStatus,
+    operator: &str,
+) -> Result<Vec<i64>, Error> {
+    Ok(sqlx::query!(
+        "UPDATE hit_and_run SET status = $1, operator = $2 \
+        WHERE id = ANY($3) AND status = 0 \
+        RETURNING uid;",
+        status as i32,
+        operator,
+        ids
+    )
+    .fetch_all(client)
+    .await?
+    .into_iter()
+    .map(|r| r.uid)
+    .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT: Rule = Rule {
+        min_seedtime: 72 * 3600,
+        min_ratio: 1.0,
+        grace_days: 14,
+    };
+
+    fn rule(hours: Option<i64>, ratio: Option<f64>, days: Option<i64>) -> HitAndRunRule {
+        HitAndRunRule {
+            tid: 1,
+            minseedhours: hours,
+            minratio: ratio,
+            gracedays: days,
+        }
+    }
+
+    #[test]
+    fn overridden_by_works() {
+        assert_eq!(DEFAULT.overridden_by(&rule(None, None, None)), DEFAULT);
+        assert_eq!(
+            DEFAULT.overridden_by(&rule(Some(24), None, None)),
+            Rule {
+                min_seedtime: 24 * 3600,
+                ..DEFAULT
+            }
+        );
+        assert_eq!(
+            DEFAULT.overridden_by(&rule(None, Some(0.5), Some(0))),
+            Rule {
+                min_ratio: 0.5,
+                grace_days: 0,
+                ..DEFAULT
+            }
+        );
+        // exempted torrent
+        let exempted = DEFAULT.overridden_by(&rule(Some(0), Some(0.0), None));
+        assert_eq!((exempted.min_seedtime, exempted.min_ratio), (0, 0.0));
+    }
+
+    #[test]
+    fn unzip_works() {
+        let rules = [
+            (1, DEFAULT),
+            (2, DEFAULT.overridden_by(&rule(Some(1), None, None))),
+        ];
+        assert_eq!(
+            Rule::unzip(&rules),
+            (
+                vec![1, 2],
+                vec![72 * 3600, 3600],
+                vec![1.0, 1.0],
+                vec![14, 14]
+            )
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use super::*;
 
 pub async fn add_invitation_code(
-    client: &sqlx::PgPool,
+    tx: &mut sqlx::PgConnection,
     sender: &str,
     code: &str,
     send_to: &str,
@@ -14,7 +14,7 @@ pub async fn add_invitation_code(
         code,
         send_to,
     )
-    .fetch_one(client)
+    .fetch_one(&mut *tx)
     .await?)
 }
 
@@ -0,0 +1,77 @@
+use super::*;
+
+pub async fn add_ip_ban(
+    client: &sqlx::PgPool,
+    cidr: &str,
+    reason: &str,
+    operator: &str,
+) -> IpBanRet {
+    Ok(sqlx::query_as!(
+        IpBan,
+        "INSERT INTO ip_ban(cidr, reason, operator) \
+        VALUES($1, $2, $3) ON CONFLICT (cidr) DO \
+        UPDATE SET reason = $2, operator = $3 \
+        RETURNING *;",
+        cidr,
+        reason,
+        operator
+    )
+    .fetch_one(client)
+    .await?)
+}
+
+/// Ban `cidrs` announced by `asn`, and lift bans on those it no longer
+/// announces. It returns the bans set and lifted.
+pub async fn replace_asn_bans(
+    client: &sqlx::PgPool,
+    asn: i64,
+    cidrs: &[String],
+    reason: &str,
+    operator: &str,
+) -> Result<(Vec<IpBan>, Vec<IpBan>), Error> {
+    let mut tx = client.begin().await?;
+    let deleted = sqlx::query_as!(
+        IpBan,
+        "DELETE FROM ip_ban \
+        WHERE asn = $1 AND NOT cidr = ANY($2) \
+        RETURNING *;",
+        asn,
+        cidrs
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let set = sqlx::query_as!(
+        IpBan,
+        "INSERT INTO ip_ban(cidr, reason, operator, asn) \
+        SELECT UNNEST($1::VARCHAR[]), $2, $3, $4 ON CONFLICT (cidr) DO \
+        UPDATE SET reason = $2, operator = $3, asn = $4 \
+        RETURNING *;",
+        cidrs,
+        reason,
+        operator,
+        asn
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((set, deleted))
+}
+
+pub async fn delete_ip_ban_by_ids(client: &sqlx::PgPool, ids: &[i64]) -> IpBanVecRet {
+    Ok(sqlx::query_as!(
+        IpBan,
+        "DELETE FROM ip_ban \
+        WHERE id = ANY($1) \
+        RETURNING *;",
+        ids
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+pub async fn list_ip_bans(client: &sqlx::PgPool) -> IpBanVecRet {
+    Ok(sqlx::query_as!(IpBan, "SELECT * FROM ip_ban ORDER BY id;")
+        .fetch_all(client)
+        .await?)
+}
@@ -0,0 +1,144 @@
+use super::*;
+
+/// why money changes
+#[repr(C)]
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum LedgerReason {
+    Bonus = 0,
+    Transfer,
+    Invitation,
+    AdminGrant,
+    ShopPurchase,
+    Penalty,
+}
+
+/// A change of money. When the balance is not enough,
+/// a penalty takes all the rest, while others fail.
+#[derive(Debug)]
+pub struct MoneyChange<'a> {
+    pub uid: i64,
+    pub amount: f64,
+    pub reason: LedgerReason,
+    /// username of the other side or operator
+    pub counterparty: Option<&'a str>,
+    pub note: Option<&'a str>,
+}
+
+impl<'a> MoneyChange<'a> {
+    pub fn new(uid: i64, amount: f64, reason: LedgerReason) -> Self {
+        MoneyChange {
+            uid,
+            amount,
+            reason,
+            counterparty: None,
+            note: None,
+        }
+    }
+
+    pub fn counterparty(mut self, counterparty: &'a str) -> Self {
+        self.counterparty = Some(counterparty);
+        self
+    }
+
+    pub fn note(mut self, note: &'a str) -> Self {
+        self.note = Some(note);
+        self
+    }
+}
+
+/// Apply a change and record it inside a transaction,
+/// every change of money must go through here.
+/// Returns the new balance.
+pub async fn record_money_change(
+    tx: &mut sqlx::PgConnection,
+    change: MoneyChange<'_>,
+) -> Result<f64, Error> {
+    let balance = sqlx::query!(
+        "SELECT money FROM user_info \
+        WHERE id = $1 FOR UPDATE;",
+        change.uid
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::NotFound)?
+    .money;
+    let mut amount = change.amount;
+    if balance + amount < 0.0 {
+        if change.reason != LedgerReason::Penalty {
+            return Err(Error::RequestError("not enough money".to_string()));
+        }
+        amount = -balance;
+    }
+    let balance = balance + amount;
+
+    sqlx::query!(
+        "UPDATE user_info SET money = $1 \
+        WHERE id = $2;",
+        balance,
+        change.uid
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO money_ledger(uid, amount, balance, reason, counterparty, note) \
+        VALUES($1, $2, $3, $4, $5, $6);",
+        change.uid,
+        amount,
+        balance,
+        change.reason as i32,
+        change.counterparty,
+        change.note
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(balance)
+}
+
+/// Apply changes in one transaction, all or nothing.
+/// Users are locked in order of uid, so that concurrent transfers
+/// between the same users never deadlock.
+pub async fn change_money(
+    client: &sqlx::PgPool,
+    mut changes: Vec<MoneyChange<'_>>,
+) -> Result<(), Error> {
+    changes.sort_by_key(|change| change.uid);
+    let mut tx = client.begin().await?;
+    for change in changes {
+        record_money_change(&mut tx, change).await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn find_ledger_by_uid(
+    client: &sqlx::PgPool,
+    uid: i64,
+    page_offset: i64,
+) -> LedgerEntryVecRet {
+    Ok(sqlx::query_as!(
+        LedgerEntry,
+        "SELECT id, uid, amount, balance, reason, counterparty, note, createTime \
+        FROM money_ledger \
+        WHERE uid = $1 \
+        ORDER BY id DESC \
+        LIMIT 20 OFFSET $2;",
+        uid,
+        page_offset
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+pub async fn query_ledger_counts_by_uid(client: &sqlx::PgPool, uid: i64) -> CountRet {
+    Ok(sqlx::query!(
+        "SELECT COUNT(*) FROM money_ledger \
+        WHERE uid = $1;",
+        uid
+    )
+    .fetch_one(client)
+    .await?
+    .count
+    .expect("sql function not right"))
+}
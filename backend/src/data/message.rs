@@ -21,6 +21,26 @@ pub async fn add_message(
     Ok(())
 }
 
+/// send a message from the system inside a transaction
+pub async fn add_system_message(
+    tx: &mut sqlx::PgConnection,
+    receiver: &str,
+    title: &str,
+    body: Option<&str>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO message(receiver, title, body, sendTime) \
+        VALUES($1, $2, $3, NOW());",
+        receiver,
+        title,
+        body
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn read_message(client: &sqlx::PgPool, ids: &[i64], receiver: &str) -> Result<(), Error> {
     sqlx::query!(
         "UPDATE message SET read = TRUE \
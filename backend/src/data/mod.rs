@@ -1,12 +1,38 @@
 pub mod activation;
+pub mod bonus;
+pub mod hit_and_run;
 pub mod invitation;
+pub mod ip_ban;
 pub mod kv;
+pub mod ledger;
 #[cfg(feature = "message")]
 pub mod message;
+/// Without feature `message` nobody is able to read messages,
+/// so system messages of jobs and notifications are dropped.
+#[cfg(not(feature = "message"))]
+pub mod message {
+    use crate::error::Error;
+
+    pub async fn add_system_message(
+        _tx: &mut sqlx::PgConnection,
+        _receiver: &str,
+        _title: &str,
+        _body: Option<&str>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
 pub mod oss;
+pub mod promotion;
 pub mod rank;
+pub mod ratio_watch;
 mod response;
+pub mod shop;
+pub mod site_event;
+pub mod stat;
+pub mod subscription;
 pub mod tag;
+pub mod token;
 pub mod torrent;
 pub mod torrent_info;
 pub mod torrent_status;
@@ -14,9 +40,12 @@ pub mod user;
 pub mod user_info;
 
 use crate::error::Error;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use response::*;
-pub use response::{Rank, TorrentStatusByUser};
+pub use response::{
+    BonusPreview, BonusTorrent, FileNode, Rank, RankCandidate, SeedingTorrent, ShopItem,
+    SiteStatus, SubscriptionList, TorrentStatusByUser,
+};
 use serde::{Deserialize, Serialize};
 use sopt_derive::ToResponse;
 use std::collections::{HashMap, HashSet};
@@ -78,6 +107,7 @@ pub trait ToResponse: Serialize {
 impl ToResponse for String {}
 impl ToResponse for HashSet<String> {}
 impl ToResponse for HashMap<String, String> {}
+impl<T: ToResponse> ToResponse for Option<T> {}
 
 /// A common wrapper used to return page count with list
 #[derive(Serialize, Debug, ToResponse)]
@@ -0,0 +1,106 @@
+use super::*;
+use std::convert::TryFrom;
+
+/// Promotion of a torrent, changes how much upload and download are credited.
+#[repr(C)]
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Promotion {
+    None = 0,
+    Free,
+    Half,
+    Thirty,
+    DoubleUpload,
+    DoubleFree,
+}
+
+impl TryFrom<i32> for Promotion {
+    type Error = Error;
+
+    fn try_from(v: i32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == Promotion::None as i32 => Ok(Promotion::None),
+            x if x == Promotion::Free as i32 => Ok(Promotion::Free),
+            x if x == Promotion::Half as i32 => Ok(Promotion::Half),
+            x if x == Promotion::Thirty as i32 => Ok(Promotion::Thirty),
+            x if x == Promotion::DoubleUpload as i32 => Ok(Promotion::DoubleUpload),
+            x if x == Promotion::DoubleFree as i32 => Ok(Promotion::DoubleFree),
+            _ => Err(Error::RequestError("unknown promotion".to_string())),
+        }
+    }
+}
+
+impl Promotion {
+    /// the promotion in effect now, `None` out of its time window
+    pub fn active(
+        promotion: i32,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Promotion {
+        let now = Utc::now();
+        if start.is_some_and(|start| start > now) || end.is_some_and(|end| end <= now) {
+            return Promotion::None;
+        }
+        Promotion::try_from(promotion).unwrap_or(Promotion::None)
+    }
+
+    /// factors of (upload, download)
+    pub fn factors(self) -> (f64, f64) {
+        match self {
+            Promotion::None => (1.0, 1.0),
+            Promotion::Free => (1.0, 0.0),
+            Promotion::Half => (1.0, 0.5),
+            Promotion::Thirty => (1.0, 0.3),
+            Promotion::DoubleUpload => (2.0, 1.0),
+            Promotion::DoubleFree => (2.0, 0.0),
+        }
+    }
+
+    /// Factors of (upload, download) when several promotions apply,
+    /// the most favorable upload and download are taken separately.
+    pub fn most_favorable(promotions: impl IntoIterator<Item = Promotion>) -> (f64, f64) {
+        promotions
+            .into_iter()
+            .map(Promotion::factors)
+            .fold((1.0, 1.0), |(up, down), (u, d)| (up.max(u), down.min(d)))
+    }
+
+    /// download is discounted
+    pub fn is_free(self) -> bool {
+        self.factors().1 < 1.0
+    }
+}
+
+/// Set promotion of torrents, which takes effect between `start` and `end`.
+/// Either of them can be left open, `Promotion::None` removes the promotion.
+pub async fn promote_torrents(
+    client: &sqlx::PgPool,
+    ids: &[i64],
+    promotion: Promotion,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE torrent_info SET promotion = $1, promotionStart = $2, promotionEnd = $3 \
+        WHERE id = ANY($4);",
+        promotion as i32,
+        start,
+        end,
+        ids
+    )
+    .execute(client)
+    .await?;
+
+    Ok(())
+}
+
+/// remove promotions which have ended, returns the number of torrents
+pub async fn expire_promotions(client: &sqlx::PgPool) -> Result<u64, Error> {
+    let ret = sqlx::query!(
+        "UPDATE torrent_info SET promotion = 0, promotionStart = NULL, promotionEnd = NULL \
+        WHERE promotion <> 0 AND promotionEnd <= now();"
+    )
+    .execute(client)
+    .await?;
+
+    Ok(ret.rows_affected())
+}
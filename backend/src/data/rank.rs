@@ -1,15 +1,19 @@
+use super::message;
 use super::*;
 
 pub async fn update_or_add_rank(client: &sqlx::PgPool, rank: Rank) -> Result<(), Error> {
     sqlx::query!(
-        "INSERT INTO rank(name, role, upload, age, next) \
-        VALUES($1, $2, $3, $4, $5) ON CONFLICT (name) DO \
-        UPDATE SET name = $1, role = $2, upload = $3, age = $4, next = $5;",
+        "INSERT INTO rank(name, role, upload, age, next, ratio, seedtime) \
+        VALUES($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (name) DO \
+        UPDATE SET name = $1, role = $2, upload = $3, age = $4, next = $5, \
+        ratio = $6, seedtime = $7;",
         rank.name,
         &rank.role,
         rank.upload,
         rank.age,
-        rank.next
+        rank.next,
+        rank.ratio,
+        rank.seedtime
     )
     .execute(client)
     .await?;
@@ -20,7 +24,7 @@ pub async fn update_or_add_rank(client: &sqlx::PgPool, rank: Rank) -> Result<(),
 pub async fn find_rank_by_username(client: &sqlx::PgPool, username: &str) -> RankRet {
     sqlx::query_as!(
         Rank,
-        "SELECT rank.id, name, role, rank.upload, age, next FROM rank \
+        "SELECT rank.id, name, role, rank.upload, age, next, ratio, rank.seedtime FROM rank \
         INNER JOIN user_info ON rank.id = user_info.rank \
         WHERE user_info.username = $1;",
         username
@@ -31,6 +35,20 @@ pub async fn find_rank_by_username(client: &sqlx::PgPool, username: &str) -> Ran
     .ok_or(Error::NotFound)
 }
 
+pub async fn find_rank_by_uid(client: &sqlx::PgPool, uid: i64) -> RankRet {
+    sqlx::query_as!(
+        Rank,
+        "SELECT rank.id, name, role, rank.upload, age, next, ratio, rank.seedtime FROM rank \
+        INNER JOIN user_info ON rank.id = user_info.rank \
+        WHERE user_info.id = $1;",
+        uid
+    )
+    .fetch_all(client)
+    .await?
+    .pop()
+    .ok_or(Error::NotFound)
+}
+
 pub async fn find_rank_by_id(client: &sqlx::PgPool, id: i32) -> RankRet {
     sqlx::query_as!(
         Rank,
@@ -49,3 +67,93 @@ pub async fn find_all_ranks(client: &sqlx::PgPool) -> RankVecRet {
         .fetch_all(client)
         .await?)
 }
+
+/// users whose rank can be changed automatically, that is,
+/// not banned and rank not locked by admin
+pub async fn find_rank_candidates(client: &sqlx::PgPool) -> RankCandidateVecRet {
+    Ok(sqlx::query_as!(
+        RankCandidate,
+        "SELECT user_info.id, user_info.rank, user_info.upload, user_info.download, \
+        user_info.registerTime, \
+        COALESCE((SELECT SUM(seedtime) FROM torrent_status WHERE uid = user_info.id), 0)::BIGINT \
+        AS \"seedtime!\" \
+        FROM user_info INNER JOIN users ON user_info.id = users.id \
+        WHERE users.role & 1 = 1 AND NOT user_info.rankLocked;"
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// Move a user from a rank to another in one transaction.
+///
+/// Roles of the new rank are given, and those only in the old one are taken.
+/// The bit of ordinary user is left alone, as it is managed by bans. The change is
+/// logged and the user is notified.
+pub async fn change_rank(
+    client: &sqlx::PgPool,
+    uid: i64,
+    from: &Rank,
+    to: &Rank,
+    reason: &str,
+    locked: bool,
+) -> Result<(), Error> {
+    let bits = |rank: &Rank| {
+        rank.role
+            .iter()
+            .fold(0i64, |mask, r| mask | (1i64 << (r % 32)))
+    };
+    // bit 0 stands for not banned
+    let give = bits(to) & !1;
+    let take = bits(from) & !give & !1;
+
+    let mut tx = client.begin().await?;
+    let username = sqlx::query!(
+        "UPDATE users SET role = (role & ~$2::BIGINT) | $3::BIGINT \
+        WHERE id = $1 RETURNING username;",
+        uid,
+        take,
+        give
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::NotFound)?
+    .username;
+    sqlx::query!(
+        "UPDATE user_info SET rank = $1, rankLocked = $2 \
+        WHERE id = $3;",
+        to.id,
+        locked,
+        uid
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO rank_log(uid, fromRank, toRank, reason) \
+        VALUES($1, $2, $3, $4);",
+        uid,
+        from.id,
+        to.id,
+        reason
+    )
+    .execute(&mut *tx)
+    .await?;
+    let body = format!(
+        "Your rank is changed from {} to {}: {}",
+        from.name, to.name, reason
+    );
+    message::add_system_message(&mut tx, &username, "Rank Changed", Some(&body)).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn find_rank_logs_by_uid(client: &sqlx::PgPool, uid: i64) -> RankLogVecRet {
+    Ok(sqlx::query_as!(
+        RankLog,
+        "SELECT * FROM rank_log \
+        WHERE uid = $1 ORDER BY id DESC;",
+        uid
+    )
+    .fetch_all(client)
+    .await?)
+}
@@ -0,0 +1,164 @@
+use super::message;
+use super::*;
+
+/// 1. Watching, warned and not allowed to download new torrents
+/// 2. Banned, ratio not recovered before deadline
+#[repr(C)]
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum RatioWatchStatus {
+    Watching = 0,
+    Banned,
+}
+
+/// Put ordinary users out of newbie term with ratio below `min_ratio`
+/// on watch until `deadline`, and warn them.
+pub async fn watch_low_ratio_users(
+    client: &sqlx::PgPool,
+    registered_before: DateTime<Utc>,
+    min_ratio: f64,
+    deadline: DateTime<Utc>,
+) -> Result<u64, Error> {
+    let mut tx = client.begin().await?;
+    let watched = sqlx::query!(
+        "WITH ret AS (\
+            INSERT INTO ratio_watch(uid, status, deadline) \
+            SELECT user_info.id, $1, $2 FROM user_info INNER JOIN users ON user_info.id = users.id \
+            WHERE users.role & 1 = 1 AND user_info.registerTime < $3 AND user_info.download > 0 \
+            AND user_info.upload::DOUBLE PRECISION / user_info.download < $4 \
+            ON CONFLICT (uid) DO NOTHING RETURNING uid\
+        ) SELECT username FROM ret INNER JOIN users ON ret.uid = users.id;",
+        RatioWatchStatus::Watching as i32,
+        deadline,
+        registered_before,
+        min_ratio
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let body = format!(
+        "Your ratio is below {}, you cannot download new torrents until it recovers. \
+        Your account will be banned if it is not recovered before {}.",
+        min_ratio, deadline
+    );
+    for user in watched.iter() {
+        message::add_system_message(&mut tx, &user.username, "Ratio Warning", Some(&body)).await?;
+    }
+    tx.commit().await?;
+
+    Ok(watched.len() as u64)
+}
+
+/// Ban users whose ratio is still below `min_ratio` after deadline.
+pub async fn ban_expired_watches(client: &sqlx::PgPool, min_ratio: f64) -> Result<u64, Error> {
+    let mut tx = client.begin().await?;
+    let banned = sqlx::query!(
+        "WITH ret AS (\
+            UPDATE ratio_watch SET status = $1 FROM user_info \
+            WHERE ratio_watch.uid = user_info.id AND ratio_watch.status = $2 \
+            AND ratio_watch.deadline <= now() \
+            AND user_info.upload::DOUBLE PRECISION / GREATEST(user_info.download, 1) < $3 \
+            RETURNING ratio_watch.uid\
+        ) UPDATE users SET role = role & ~1::BIGINT FROM ret \
+        WHERE users.id = ret.uid RETURNING username;",
+        RatioWatchStatus::Banned as i32,
+        RatioWatchStatus::Watching as i32,
+        min_ratio
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for user in banned.iter() {
+        message::add_system_message(
+            &mut tx,
+            &user.username,
+            "Banned For Low Ratio",
+            Some(
+                "Your ratio was not recovered in time. It will be lifted once your ratio recovers.",
+            ),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(banned.len() as u64)
+}
+
+/// Lift watches and bans of users whose ratio has recovered.
+pub async fn lift_recovered_watches(client: &sqlx::PgPool, min_ratio: f64) -> Result<u64, Error> {
+    let mut tx = client.begin().await?;
+    let lifted = sqlx::query!(
+        "DELETE FROM ratio_watch USING user_info \
+        WHERE ratio_watch.uid = user_info.id \
+        AND user_info.upload::DOUBLE PRECISION / GREATEST(user_info.download, 1) >= $1 \
+        RETURNING ratio_watch.uid, ratio_watch.status;",
+        min_ratio
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let unbanned: Vec<i64> = lifted
+        .iter()
+        .filter(|r| r.status == RatioWatchStatus::Banned as i32)
+        .map(|r| r.uid)
+        .collect();
+    sqlx::query!(
+        "UPDATE users SET role = role | 1::BIGINT \
+        WHERE id = ANY($1);",
+        &unbanned
+    )
+    .execute(&mut *tx)
+    .await?;
+    let ids: Vec<i64> = lifted.iter().map(|r| r.uid).collect();
+    let usernames = sqlx::query!("SELECT username FROM users WHERE id = ANY($1);", &ids)
+        .fetch_all(&mut *tx)
+        .await?;
+    for user in usernames.iter() {
+        message::add_system_message(
+            &mut tx,
+            &user.username,
+            "Ratio Recovered",
+            Some("Your ratio has recovered, restrictions are lifted."),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(lifted.len() as u64)
+}
+
+pub async fn find_ratio_watch_by_uid(
+    client: &sqlx::PgPool,
+    uid: i64,
+) -> Result<Option<RatioWatch>, Error> {
+    Ok(sqlx::query_as!(
+        RatioWatch,
+        "SELECT ratio_watch.uid, users.username, status, startTime, deadline \
+        FROM ratio_watch INNER JOIN users ON ratio_watch.uid = users.id \
+        WHERE uid = $1;",
+        uid
+    )
+    .fetch_optional(client)
+    .await?)
+}
+
+/// A user banned by admin is no longer watched, so that
+/// a recovered ratio never lifts the manual ban.
+pub async fn delete_ratio_watch_by_uid(client: &sqlx::PgPool, uid: i64) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM ratio_watch \
+        WHERE uid = $1;",
+        uid
+    )
+    .execute(client)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_ratio_watches(client: &sqlx::PgPool) -> RatioWatchVecRet {
+    Ok(sqlx::query_as!(
+        RatioWatch,
+        "SELECT ratio_watch.uid, users.username, status, startTime, deadline \
+        FROM ratio_watch INNER JOIN users ON ratio_watch.uid = users.id \
+        ORDER BY deadline;"
+    )
+    .fetch_all(client)
+    .await?)
+}
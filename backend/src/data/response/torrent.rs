@@ -4,13 +4,28 @@ pub(crate) type TorrentIdRet = Result<TorrentId, Error>;
 pub(crate) type MiniTorrentRet = Result<MiniTorrent, Error>;
 pub(crate) type MiniTorrentVecRet = Result<Vec<MiniTorrent>, Error>;
 pub(crate) type SlimTorrentVecRet = Result<Vec<SlimTorrent>, Error>;
+pub(crate) type ReviewTorrentVecRet = Result<Vec<ReviewTorrent>, Error>;
 pub(crate) type FullTorrentRet = Result<FullTorrent, Error>;
+pub(crate) type FeedTorrentVecRet = Result<Vec<FeedTorrent>, Error>;
 
 pub(crate) type TagVecRet = Result<Vec<Tag>, Error>;
 
 pub(crate) type TorrentStatusVecRet = Result<Vec<TorrentStatus>, Error>;
 pub(crate) type PersonalTorrentVecRet = Result<Vec<PersonalTorrent>, Error>;
 
+pub(crate) type HitAndRunVecRet = Result<Vec<HitAndRun>, Error>;
+pub(crate) type HitAndRunRuleRet = Result<HitAndRunRule, Error>;
+pub(crate) type HitAndRunRuleVecRet = Result<Vec<HitAndRunRule>, Error>;
+
+pub(crate) type SiteEventRet = Result<SiteEvent, Error>;
+pub(crate) type SiteEventVecRet = Result<Vec<SiteEvent>, Error>;
+
+pub(crate) type SeedingTorrentVecRet = Result<Vec<SeedingTorrent>, Error>;
+
+pub(crate) type SubscriptionRet = Result<Subscription, Error>;
+pub(crate) type SubscriptionVecRet = Result<Vec<Subscription>, Error>;
+pub(crate) type ActiveSubscriptionVecRet = Result<Vec<ActiveSubscription>, Error>;
+
 #[derive(Serialize, Debug, ToResponse)]
 pub struct TorrentId {
     pub id: i64,
@@ -19,9 +34,13 @@ pub struct TorrentId {
 
 #[derive(Debug)]
 pub struct MiniTorrent {
+    pub id: i64,
+    pub title: String,
     pub poster: String,
     pub visible: bool,
-    pub free: bool,
+    pub promotion: i32,
+    pub promotionstart: Option<DateTime<Utc>>,
+    pub promotionend: Option<DateTime<Utc>>,
     pub tag: Option<Vec<String>>,
     pub length: i64,
 }
@@ -35,12 +54,33 @@ pub struct SlimTorrent {
     #[serde(rename = "lastEdit")]
     pub lastedit: DateTime<Utc>,
     pub length: i64,
-    pub free: bool,
+    pub promotion: i32,
+    #[serde(rename = "promotionStart")]
+    pub promotionstart: Option<DateTime<Utc>>,
+    #[serde(rename = "promotionEnd")]
+    pub promotionend: Option<DateTime<Utc>>,
     pub downloading: i32,
     pub uploading: i32,
     pub finished: i64,
 }
 
+/// torrents waiting for review, `dupeOf` is the probable duplicate
+/// found when uploading, with similarity `dupeScore`
+#[derive(Serialize, Debug, ToResponse)]
+pub struct ReviewTorrent {
+    pub id: i64,
+    pub title: String,
+    pub poster: String,
+    pub tag: Option<Vec<String>>,
+    #[serde(rename = "lastEdit")]
+    pub lastedit: DateTime<Utc>,
+    pub length: i64,
+    #[serde(rename = "dupeOf")]
+    pub dupeof: Option<i64>,
+    #[serde(rename = "dupeScore")]
+    pub dupescore: Option<f64>,
+}
+
 #[derive(Serialize, Debug, ToResponse)]
 pub struct FullTorrent {
     pub id: i64,
@@ -53,7 +93,11 @@ pub struct FullTorrent {
     pub createtime: DateTime<Utc>,
     #[serde(rename = "lastEdit")]
     pub lastedit: DateTime<Utc>,
-    pub free: bool,
+    pub promotion: i32,
+    #[serde(rename = "promotionStart")]
+    pub promotionstart: Option<DateTime<Utc>>,
+    #[serde(rename = "promotionEnd")]
+    pub promotionend: Option<DateTime<Utc>>,
     pub downloading: i32,
     pub uploading: i32,
     pub finished: i64,
@@ -62,6 +106,31 @@ pub struct FullTorrent {
     pub infohash: Option<String>,
 }
 
+/// torrents listed in feeds
+#[derive(Debug)]
+pub struct FeedTorrent {
+    pub id: i64,
+    pub title: String,
+    pub poster: String,
+    pub description: Option<String>,
+    pub tag: Option<Vec<String>>,
+    pub lastedit: DateTime<Utc>,
+    pub length: i64,
+    pub promotion: i32,
+    pub promotionstart: Option<DateTime<Utc>>,
+    pub promotionend: Option<DateTime<Utc>>,
+}
+
+/// a node of torrent file tree, `size` and `count` of a directory
+/// cover all files under it, `children` is null for a file
+#[derive(Serialize, Debug, PartialEq, ToResponse)]
+pub struct FileNode {
+    pub name: String,
+    pub size: i64,
+    pub count: i64,
+    pub children: Option<Vec<FileNode>>,
+}
+
 #[derive(Serialize, Debug, ToResponse)]
 pub struct Tag {
     pub name: String,
@@ -76,6 +145,10 @@ pub struct TorrentStatus {
     pub upload: i64,
     pub download: i64,
     pub finished: bool,
+    pub seedtime: i64,
+    pub lastannounce: DateTime<Utc>,
+    pub finishtime: Option<DateTime<Utc>>,
+    pub seeding: bool,
 }
 
 #[derive(Serialize, Debug, ToResponse)]
@@ -85,7 +158,11 @@ pub struct PersonalTorrent {
     pub length: i64,
     pub upload: i64,
     pub download: i64,
-    pub free: bool,
+    pub promotion: i32,
+    #[serde(rename = "promotionStart")]
+    pub promotionstart: Option<DateTime<Utc>>,
+    #[serde(rename = "promotionEnd")]
+    pub promotionend: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Debug, ToResponse)]
@@ -95,3 +172,103 @@ pub struct TorrentStatusByUser {
     pub finished: Vec<PersonalTorrent>,
     pub unfinished: Vec<PersonalTorrent>,
 }
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct HitAndRun {
+    pub id: i64,
+    pub tid: i64,
+    pub title: String,
+    pub uid: i64,
+    pub username: String,
+    pub seedtime: i64,
+    pub upload: i64,
+    pub status: i32,
+    pub operator: Option<String>,
+    #[serde(rename = "createTime")]
+    pub createtime: DateTime<Utc>,
+}
+
+/// hit and run rule of a torrent, unset ones fall back to site settings
+#[derive(Serialize, Debug, ToResponse)]
+pub struct HitAndRunRule {
+    pub tid: i64,
+    #[serde(rename = "minSeedHours")]
+    pub minseedhours: Option<i64>,
+    #[serde(rename = "minRatio")]
+    pub minratio: Option<f64>,
+    #[serde(rename = "graceDays")]
+    pub gracedays: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct SeedingTorrent {
+    pub uid: i64,
+    pub tid: i64,
+    pub title: String,
+    pub length: i64,
+    pub createtime: DateTime<Utc>,
+    pub seeders: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BonusTorrent {
+    pub tid: i64,
+    pub title: String,
+    pub weight: f64,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct BonusPreview {
+    pub torrents: Vec<BonusTorrent>,
+    pub weight: f64,
+    pub hourly: f64,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct SiteEvent {
+    pub id: i64,
+    pub name: String,
+    pub promotion: i32,
+    pub tag: Option<Vec<String>>,
+    #[serde(rename = "startTime")]
+    pub starttime: DateTime<Utc>,
+    #[serde(rename = "endTime")]
+    pub endtime: DateTime<Utc>,
+    pub operator: String,
+    #[serde(rename = "createTime")]
+    pub createtime: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct SiteStatus {
+    pub name: String,
+    pub events: Vec<SiteEvent>,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct Subscription {
+    pub id: i64,
+    pub name: String,
+    pub keywords: Vec<String>,
+    pub tags: Vec<String>,
+    #[serde(rename = "createTime")]
+    pub createtime: DateTime<Utc>,
+}
+
+/// subscriptions of a user, and whether matched torrents are notified in digests
+#[derive(Serialize, Debug, ToResponse)]
+pub struct SubscriptionList {
+    pub digest: bool,
+    pub subscriptions: Vec<Subscription>,
+}
+
+/// subscriptions of users not banned, matched when torrents are accepted
+#[derive(Debug)]
+pub struct ActiveSubscription {
+    pub id: i64,
+    pub uid: i64,
+    pub username: String,
+    pub keywords: Vec<String>,
+    pub tags: Vec<String>,
+    pub digest: bool,
+}
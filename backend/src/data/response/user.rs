@@ -13,10 +13,32 @@ pub type InvitationVecRet = Result<Vec<Invitation>, Error>;
 pub type RankRet = Result<Rank, Error>;
 pub type RankVecRet = Result<Vec<Rank>, Error>;
 
+pub type RankCandidateVecRet = Result<Vec<RankCandidate>, Error>;
+pub type RankLogVecRet = Result<Vec<RankLog>, Error>;
+
+pub type RatioWatchVecRet = Result<Vec<RatioWatch>, Error>;
+
+pub type UserStatVecRet = Result<Vec<UserStat>, Error>;
+pub type SiteStatVecRet = Result<Vec<SiteStat>, Error>;
+
 pub type MessageVecRet = Result<Vec<Message>, Error>;
 
 pub type ActivationRet = Result<Activation, Error>;
 
+pub type ShopItemRet = Result<ShopItem, Error>;
+pub type ShopItemVecRet = Result<Vec<ShopItem>, Error>;
+pub type PurchaseRet = Result<Purchase, Error>;
+pub type PurchaseVecRet = Result<Vec<Purchase>, Error>;
+
+pub type UserTokenVecRet = Result<Vec<UserToken>, Error>;
+pub type TokenUsageRet = Result<TokenUsage, Error>;
+pub type TokenUsageVecRet = Result<Vec<TokenUsage>, Error>;
+
+pub type LedgerEntryVecRet = Result<Vec<LedgerEntry>, Error>;
+
+pub type IpBanRet = Result<IpBan, Error>;
+pub type IpBanVecRet = Result<Vec<IpBan>, Error>;
+
 #[derive(Serialize, Debug, ToResponse)]
 pub struct Account {
     pub id: i64,
@@ -61,6 +83,8 @@ pub struct User {
     pub privacy: i32,
     pub email: String,
     pub passkey: String,
+    pub invites: i32,
+    pub title: Option<String>,
 }
 
 #[derive(Serialize, Debug, ToResponse)]
@@ -79,12 +103,76 @@ pub struct Rank {
     pub upload: i64,
     pub age: i64,
     pub next: Option<i32>,
+    #[serde(default)]
+    pub ratio: f64,
+    /// total seeding time in second
+    #[serde(default)]
+    pub seedtime: i64,
+}
+
+/// what is needed to evaluate the rank of a user
+#[derive(Debug)]
+pub struct RankCandidate {
+    pub id: i64,
+    pub rank: i32,
+    pub upload: i64,
+    pub download: i64,
+    pub registertime: DateTime<Utc>,
+    pub seedtime: i64,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct RankLog {
+    pub id: i64,
+    pub uid: i64,
+    #[serde(rename = "fromRank")]
+    pub fromrank: i32,
+    #[serde(rename = "toRank")]
+    pub torank: i32,
+    pub reason: String,
+    #[serde(rename = "createTime")]
+    pub createtime: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct RatioWatch {
+    pub uid: i64,
+    pub username: String,
+    pub status: i32,
+    #[serde(rename = "startTime")]
+    pub starttime: DateTime<Utc>,
+    pub deadline: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct UserStat {
+    pub day: NaiveDate,
+    pub upload: i64,
+    pub download: i64,
+    pub seeding: i32,
+    #[serde(rename = "seedSize")]
+    pub seedsize: i64,
+    pub bonus: f64,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct SiteStat {
+    pub day: NaiveDate,
+    pub users: i64,
+    pub seeders: i64,
+    pub upload: i64,
+    pub download: i64,
+    pub seeding: i64,
+    #[serde(rename = "seedSize")]
+    pub seedsize: i64,
+    pub bonus: f64,
 }
 
 #[derive(Serialize, Debug, ToResponse)]
 pub struct Message {
     pub id: i64,
-    pub sender: String,
+    /// `None` for messages sent by the system
+    pub sender: Option<String>,
     pub receiver: String,
     pub title: String,
     pub body: Option<String>,
@@ -99,3 +187,71 @@ pub struct Activation {
     pub code: String,
     pub used: bool,
 }
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct IpBan {
+    pub id: i64,
+    pub cidr: String,
+    pub reason: String,
+    pub operator: String,
+    #[serde(rename = "createTime")]
+    pub createtime: DateTime<Utc>,
+    pub asn: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToResponse)]
+pub struct ShopItem {
+    #[serde(default)]
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub kind: i32,
+    pub value: i64,
+    pub price: f64,
+    pub stock: Option<i64>,
+    #[serde(rename = "userLimit")]
+    pub userlimit: Option<i32>,
+    pub available: bool,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct Purchase {
+    pub id: i64,
+    pub item: i64,
+    pub name: String,
+    pub price: f64,
+    pub detail: Option<String>,
+    #[serde(rename = "createTime")]
+    pub createtime: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub uid: i64,
+    pub amount: f64,
+    pub balance: f64,
+    pub reason: i32,
+    pub counterparty: Option<String>,
+    pub note: Option<String>,
+    #[serde(rename = "createTime")]
+    pub createtime: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct UserToken {
+    pub kind: i32,
+    pub amount: i32,
+}
+
+#[derive(Serialize, Debug, ToResponse)]
+pub struct TokenUsage {
+    pub id: i64,
+    pub tid: i64,
+    pub title: String,
+    pub kind: i32,
+    #[serde(rename = "startTime")]
+    pub starttime: DateTime<Utc>,
+    #[serde(rename = "endTime")]
+    pub endtime: DateTime<Utc>,
+}
@@ -0,0 +1,241 @@
+use super::hit_and_run::HitAndRunStatus;
+use super::ledger::{self, LedgerReason, MoneyChange};
+use super::token::{self, TokenKind};
+use super::*;
+use std::convert::TryFrom;
+
+/// what a shop item gives, `value` of the item means
+/// 1. UploadCredit, bytes added to upload
+/// 2. InviteSlot, number of invitations can be sent for free
+/// 3. Title, nothing, title is given when purchasing
+/// 4. FreeleechToken, number of tokens
+/// 5. HitAndRunRemoval, nothing, hit and run id is given when purchasing
+/// 6. DoubleUploadToken, number of tokens
+#[repr(C)]
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum ShopItemKind {
+    UploadCredit = 0,
+    InviteSlot,
+    Title,
+    FreeleechToken,
+    HitAndRunRemoval,
+    DoubleUploadToken,
+}
+
+impl TryFrom<i32> for ShopItemKind {
+    type Error = Error;
+
+    fn try_from(v: i32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ShopItemKind::UploadCredit as i32 => Ok(ShopItemKind::UploadCredit),
+            x if x == ShopItemKind::InviteSlot as i32 => Ok(ShopItemKind::InviteSlot),
+            x if x == ShopItemKind::Title as i32 => Ok(ShopItemKind::Title),
+            x if x == ShopItemKind::FreeleechToken as i32 => Ok(ShopItemKind::FreeleechToken),
+            x if x == ShopItemKind::HitAndRunRemoval as i32 => Ok(ShopItemKind::HitAndRunRemoval),
+            x if x == ShopItemKind::DoubleUploadToken as i32 => Ok(ShopItemKind::DoubleUploadToken),
+            _ => Err(Error::RequestError("unknown item kind".to_string())),
+        }
+    }
+}
+
+pub async fn update_or_add_shop_item(client: &sqlx::PgPool, item: &ShopItem) -> ShopItemRet {
+    ShopItemKind::try_from(item.kind)?;
+    if item.id == 0 {
+        Ok(sqlx::query_as!(
+            ShopItem,
+            "INSERT INTO shop_item(name, description, kind, value, price, stock, userLimit, available) \
+            VALUES($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *;",
+            item.name,
+            item.description,
+            item.kind,
+            item.value,
+            item.price,
+            item.stock,
+            item.userlimit,
+            item.available
+        )
+        .fetch_one(client)
+        .await?)
+    } else {
+        sqlx::query_as!(
+            ShopItem,
+            "UPDATE shop_item SET name = $1, description = $2, kind = $3, value = $4, \
+            price = $5, stock = $6, userLimit = $7, available = $8 \
+            WHERE id = $9 RETURNING *;",
+            item.name,
+            item.description,
+            item.kind,
+            item.value,
+            item.price,
+            item.stock,
+            item.userlimit,
+            item.available,
+            item.id
+        )
+        .fetch_all(client)
+        .await?
+        .pop()
+        .ok_or(Error::NotFound)
+    }
+}
+
+/// list items on sale, or all items including unavailable ones
+pub async fn list_shop_items(client: &sqlx::PgPool, all: bool) -> ShopItemVecRet {
+    Ok(sqlx::query_as!(
+        ShopItem,
+        "SELECT * FROM shop_item \
+        WHERE available OR $1 ORDER BY id;",
+        all
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+pub async fn find_purchases_by_uid(client: &sqlx::PgPool, uid: i64) -> PurchaseVecRet {
+    Ok(sqlx::query_as!(
+        Purchase,
+        "SELECT purchase.id, purchase.item, shop_item.name, purchase.price, purchase.detail, \
+        purchase.createTime FROM purchase INNER JOIN shop_item ON purchase.item = shop_item.id \
+        WHERE uid = $1 ORDER BY purchase.id DESC;",
+        uid
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// Purchase an item in one transaction, nothing changes if any step fails.
+///
+/// 1. lock the item and check stock and limit per user
+/// 2. deduct money, fails if not enough
+/// 3. give what the item gives
+/// 4. record the purchase
+pub async fn purchase_item(
+    client: &sqlx::PgPool,
+    uid: i64,
+    item_id: i64,
+    detail: Option<&str>,
+) -> PurchaseRet {
+    let mut tx = client.begin().await?;
+    let item = sqlx::query_as!(
+        ShopItem,
+        "SELECT * FROM shop_item \
+        WHERE id = $1 AND available FOR UPDATE;",
+        item_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    if item.stock.is_some_and(|stock| stock <= 0) {
+        return Err(Error::RequestError("out of stock".to_string()));
+    }
+    if let Some(limit) = item.userlimit {
+        let bought = sqlx::query!(
+            "SELECT COUNT(*) FROM purchase \
+            WHERE uid = $1 AND item = $2;",
+            uid,
+            item.id
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .count
+        .unwrap_or(0);
+        if bought >= limit as i64 {
+            return Err(Error::RequestError("purchase limit reached".to_string()));
+        }
+    }
+
+    ledger::record_money_change(
+        &mut tx,
+        MoneyChange::new(uid, -item.price, LedgerReason::ShopPurchase).note(&item.name),
+    )
+    .await?;
+    if item.stock.is_some() {
+        sqlx::query!(
+            "UPDATE shop_item SET stock = stock - 1 \
+            WHERE id = $1;",
+            item.id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    match ShopItemKind::try_from(item.kind)? {
+        ShopItemKind::UploadCredit => {
+            sqlx::query!(
+                "UPDATE user_info SET upload = upload + $1 \
+                WHERE id = $2;",
+                item.value,
+                uid
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        ShopItemKind::InviteSlot => {
+            sqlx::query!(
+                "UPDATE user_info SET invites = invites + $1 \
+                WHERE id = $2;",
+                item.value as i32,
+                uid
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        ShopItemKind::Title => {
+            let title = detail
+                .map(str::trim)
+                .filter(|t| !t.is_empty() && t.chars().count() <= 30)
+                .ok_or_else(|| Error::RequestError("title must be 1 to 30 chars".to_string()))?;
+            sqlx::query!(
+                "UPDATE user_info SET title = $1 \
+                WHERE id = $2;",
+                title,
+                uid
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        ShopItemKind::FreeleechToken => {
+            token::add_tokens(&mut tx, uid, TokenKind::Freeleech, item.value as i32).await?;
+        }
+        ShopItemKind::DoubleUploadToken => {
+            token::add_tokens(&mut tx, uid, TokenKind::DoubleUpload, item.value as i32).await?;
+        }
+        ShopItemKind::HitAndRunRemoval => {
+            let id = detail
+                .and_then(|d| d.trim().parse::<i64>().ok())
+                .ok_or_else(|| Error::RequestError("hit and run id needed".to_string()))?;
+            let ret = sqlx::query!(
+                "UPDATE hit_and_run SET status = $1, operator = 'shop' \
+                WHERE id = $2 AND uid = $3 AND status = $4;",
+                HitAndRunStatus::Pardoned as i32,
+                id,
+                uid,
+                HitAndRunStatus::Marked as i32
+            )
+            .execute(&mut *tx)
+            .await?;
+            if ret.rows_affected() == 0 {
+                return Err(Error::RequestError("no such hit and run".to_string()));
+            }
+        }
+    }
+
+    let purchase = sqlx::query_as!(
+        Purchase,
+        "WITH ret AS (\
+            INSERT INTO purchase(uid, item, price, detail) \
+            VALUES($1, $2, $3, $4) RETURNING *\
+        ) SELECT ret.id, ret.item, shop_item.name, ret.price, ret.detail, ret.createTime \
+        FROM ret INNER JOIN shop_item ON ret.item = shop_item.id;",
+        uid,
+        item.id,
+        item.price,
+        detail
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(purchase)
+}
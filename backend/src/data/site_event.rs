@@ -0,0 +1,81 @@
+use super::promotion::Promotion;
+use super::*;
+use std::convert::TryFrom;
+
+/// Schedule an event, during which torrents with any of `tags`
+/// (or all torrents if no tag given) get the promotion.
+pub async fn add_site_event(
+    client: &sqlx::PgPool,
+    name: &str,
+    promotion: Promotion,
+    tags: Option<&[String]>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    operator: &str,
+) -> SiteEventRet {
+    Ok(sqlx::query_as!(
+        SiteEvent,
+        "INSERT INTO site_event(name, promotion, tag, startTime, endTime, operator) \
+        VALUES($1, $2, $3, $4, $5, $6) \
+        RETURNING *;",
+        name,
+        promotion as i32,
+        tags,
+        start,
+        end,
+        operator
+    )
+    .fetch_one(client)
+    .await?)
+}
+
+pub async fn delete_site_event_by_ids(client: &sqlx::PgPool, ids: &[i64]) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM site_event \
+        WHERE id = ANY($1);",
+        ids
+    )
+    .execute(client)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_site_events(client: &sqlx::PgPool) -> SiteEventVecRet {
+    Ok(sqlx::query_as!(
+        SiteEvent,
+        "SELECT * FROM site_event ORDER BY startTime DESC;"
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// events going on now
+pub async fn find_active_events(client: &sqlx::PgPool) -> SiteEventVecRet {
+    Ok(sqlx::query_as!(
+        SiteEvent,
+        "SELECT * FROM site_event \
+        WHERE now() >= startTime AND now() < endTime \
+        ORDER BY startTime;"
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// promotions of events going on now which cover the torrent
+pub async fn find_active_promotions_by_tag(
+    client: &sqlx::PgPool,
+    tags: &[String],
+) -> Result<Vec<Promotion>, Error> {
+    sqlx::query!(
+        "SELECT promotion FROM site_event \
+        WHERE now() >= startTime AND now() < endTime \
+        AND (tag IS NULL OR cardinality(tag) = 0 OR tag && $1);",
+        tags
+    )
+    .fetch_all(client)
+    .await?
+    .into_iter()
+    .map(|r| Promotion::try_from(r.promotion))
+    .collect()
+}
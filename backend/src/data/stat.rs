@@ -0,0 +1,66 @@
+use super::*;
+
+/// Take a snapshot of today for every user, or refresh it if taken.
+///
+/// Torrents announced as seeding since `since` are counted as seeding,
+/// and bonus is what is awarded today.
+pub async fn snapshot_user_stats(
+    client: &sqlx::PgPool,
+    since: DateTime<Utc>,
+) -> Result<u64, Error> {
+    let ret = sqlx::query!(
+        "INSERT INTO user_stat(uid, day, upload, download, seeding, seedSize, bonus) \
+        SELECT user_info.id, CURRENT_DATE, user_info.upload, user_info.download, \
+        COALESCE(s.seeding, 0), COALESCE(s.size, 0), COALESCE(b.bonus, 0.0) \
+        FROM user_info \
+        LEFT JOIN (\
+            SELECT torrent_status.uid, COUNT(*)::INTEGER AS seeding, SUM(torrent.length)::BIGINT AS size \
+            FROM torrent_status INNER JOIN torrent ON torrent_status.tid = torrent.id \
+            WHERE torrent_status.seeding AND torrent_status.status <> 2 \
+            AND torrent_status.lastannounce > $1 \
+            GROUP BY torrent_status.uid\
+        ) AS s ON user_info.id = s.uid \
+        LEFT JOIN (\
+            SELECT uid, SUM(amount) AS bonus FROM bonus_award \
+            WHERE createTime >= CURRENT_DATE GROUP BY uid\
+        ) AS b ON user_info.id = b.uid \
+        ON CONFLICT (uid, day) DO UPDATE SET \
+        upload = EXCLUDED.upload, download = EXCLUDED.download, seeding = EXCLUDED.seeding, \
+        seedSize = EXCLUDED.seedSize, bonus = EXCLUDED.bonus;",
+        since
+    )
+    .execute(client)
+    .await?;
+
+    Ok(ret.rows_affected())
+}
+
+pub async fn find_user_stats(client: &sqlx::PgPool, uid: i64, days: i32) -> UserStatVecRet {
+    Ok(sqlx::query_as!(
+        UserStat,
+        "SELECT day, upload, download, seeding, seedSize, bonus FROM user_stat \
+        WHERE uid = $1 AND day > CURRENT_DATE - $2::INTEGER \
+        ORDER BY day;",
+        uid,
+        days
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// totals of all users per day, seeders counts those seeding
+pub async fn find_site_stats(client: &sqlx::PgPool, days: i32) -> SiteStatVecRet {
+    Ok(sqlx::query_as!(
+        SiteStat,
+        "SELECT day, COUNT(*) AS \"users!\", COUNT(*) FILTER (WHERE seeding > 0) AS \"seeders!\", \
+        SUM(upload)::BIGINT AS \"upload!\", SUM(download)::BIGINT AS \"download!\", \
+        SUM(seeding)::BIGINT AS \"seeding!\", SUM(seedSize)::BIGINT AS \"seedsize!\", \
+        SUM(bonus) AS \"bonus!\" \
+        FROM user_stat \
+        WHERE day > CURRENT_DATE - $1::INTEGER \
+        GROUP BY day ORDER BY day;",
+        days
+    )
+    .fetch_all(client)
+    .await?)
+}
@@ -0,0 +1,228 @@
+use super::message;
+use super::*;
+
+pub async fn add_subscription(
+    client: &sqlx::PgPool,
+    uid: i64,
+    name: &str,
+    keywords: &[String],
+    tags: &[String],
+) -> SubscriptionRet {
+    Ok(sqlx::query_as!(
+        Subscription,
+        "INSERT INTO subscription(uid, name, keywords, tags) \
+        VALUES($1, $2, $3, $4) RETURNING id, name, keywords, tags, createTime;",
+        uid,
+        name,
+        keywords,
+        tags
+    )
+    .fetch_one(client)
+    .await?)
+}
+
+pub async fn delete_subscriptions(
+    client: &sqlx::PgPool,
+    ids: &[i64],
+    uid: i64,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM subscription \
+        WHERE id = ANY($1) AND uid = $2;",
+        ids,
+        uid
+    )
+    .execute(client)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_subscriptions_by_uid(client: &sqlx::PgPool, uid: i64) -> SubscriptionVecRet {
+    Ok(sqlx::query_as!(
+        Subscription,
+        "SELECT id, name, keywords, tags, createTime FROM subscription \
+        WHERE uid = $1 ORDER BY id;",
+        uid
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+pub async fn query_subscription_counts_by_uid(client: &sqlx::PgPool, uid: i64) -> CountRet {
+    Ok(sqlx::query!(
+        "SELECT COUNT(*) FROM subscription \
+        WHERE uid = $1;",
+        uid
+    )
+    .fetch_one(client)
+    .await?
+    .count
+    .unwrap_or(0))
+}
+
+pub async fn find_digest_by_uid(client: &sqlx::PgPool, uid: i64) -> Result<bool, Error> {
+    Ok(sqlx::query!(
+        "SELECT subscriptionDigest FROM user_info \
+        WHERE id = $1;",
+        uid
+    )
+    .fetch_optional(client)
+    .await?
+    .ok_or(Error::NotFound)?
+    .subscriptiondigest)
+}
+
+/// in digest mode matched torrents are notified together instead of at once
+pub async fn update_digest_by_uid(
+    client: &sqlx::PgPool,
+    uid: i64,
+    digest: bool,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE user_info SET subscriptionDigest = $1 \
+        WHERE id = $2;",
+        digest,
+        uid
+    )
+    .execute(client)
+    .await?;
+
+    Ok(())
+}
+
+/// subscriptions of all users not banned, having only tags among `tags`
+pub async fn find_active_subscriptions(
+    tx: &mut sqlx::PgConnection,
+    tags: &[String],
+) -> ActiveSubscriptionVecRet {
+    Ok(sqlx::query_as!(
+        ActiveSubscription,
+        "SELECT subscription.id, subscription.uid, users.username, keywords, tags, \
+        user_info.subscriptionDigest AS digest FROM subscription \
+        INNER JOIN users ON subscription.uid = users.id \
+        INNER JOIN user_info ON subscription.uid = user_info.id \
+        WHERE users.role & 1 = 1 AND tags <@ $1::VARCHAR[];",
+        tags
+    )
+    .fetch_all(&mut *tx)
+    .await?)
+}
+
+/// Record matches of (uid, sid, tid), a torrent already matched for a user is skipped.
+/// It returns users having new matches.
+pub async fn add_matches(
+    tx: &mut sqlx::PgConnection,
+    matches: &[(i64, i64, i64)],
+) -> Result<Vec<i64>, Error> {
+    let uids: Vec<i64> = matches.iter().map(|m| m.0).collect();
+    let sids: Vec<i64> = matches.iter().map(|m| m.1).collect();
+    let tids: Vec<i64> = matches.iter().map(|m| m.2).collect();
+    Ok(sqlx::query!(
+        "WITH ret AS (\
+            INSERT INTO subscription_match(uid, sid, tid) \
+            SELECT * FROM UNNEST($1::BIGINT[], $2::BIGINT[], $3::BIGINT[]) \
+            ON CONFLICT (uid, tid) DO NOTHING RETURNING uid\
+        ) SELECT DISTINCT uid AS \"uid!\" FROM ret;",
+        &uids,
+        &sids,
+        &tids
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|r| r.uid)
+    .collect())
+}
+
+/// Users of the mode having torrents not notified, matched before `matched_before`.
+pub async fn find_pending_uids(
+    client: &sqlx::PgPool,
+    digest: bool,
+    matched_before: DateTime<Utc>,
+) -> Result<Vec<i64>, Error> {
+    Ok(sqlx::query!(
+        "SELECT subscription_match.uid FROM subscription_match \
+        INNER JOIN user_info ON subscription_match.uid = user_info.id \
+        WHERE notifyTime IS NULL AND subscriptionDigest = $1 \
+        GROUP BY subscription_match.uid HAVING MIN(matchTime) <= $2;",
+        digest,
+        matched_before
+    )
+    .fetch_all(client)
+    .await?
+    .into_iter()
+    .map(|r| r.uid)
+    .collect())
+}
+
+/// Notify a user of matched torrents in one message, oldest first.
+///
+/// With `hourly_limit`, torrents notified in the last hour count toward
+/// the limit and the rest are left for later. Without it, every matched
+/// torrent is notified as a digest.
+pub async fn notify_matches(
+    client: &sqlx::PgPool,
+    uid: i64,
+    hourly_limit: Option<i64>,
+) -> Result<u64, Error> {
+    let mut tx = client.begin().await?;
+    let user = sqlx::query!(
+        "SELECT username FROM users \
+        WHERE id = $1 FOR UPDATE;",
+        uid
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let limit = match hourly_limit {
+        Some(hourly_limit) => {
+            let notified = sqlx::query!(
+                "SELECT COUNT(*) FROM subscription_match \
+                WHERE uid = $1 AND notifyTime > now() - INTERVAL '1 hour';",
+                uid
+            )
+            .fetch_one(&mut *tx)
+            .await?
+            .count
+            .unwrap_or(0);
+            hourly_limit - notified
+        }
+        None => i64::MAX,
+    };
+    if limit <= 0 {
+        return Ok(0);
+    }
+
+    let matched = sqlx::query!(
+        "WITH ret AS (\
+            UPDATE subscription_match SET notifyTime = now() \
+            WHERE uid = $1 AND tid IN (\
+                SELECT tid FROM subscription_match \
+                WHERE uid = $1 AND notifyTime IS NULL ORDER BY matchTime, tid LIMIT $2\
+            ) RETURNING tid, sid\
+        ) SELECT ret.tid AS \"tid!\", torrent_info.title, subscription.name FROM ret \
+        INNER JOIN torrent_info ON ret.tid = torrent_info.id \
+        INNER JOIN subscription ON ret.sid = subscription.id ORDER BY ret.tid;",
+        uid,
+        limit
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    if matched.is_empty() {
+        return Ok(0);
+    }
+    let body = matched
+        .iter()
+        .map(|m| format!("[{}] {} (id: {})", m.name, m.title, m.tid))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let title = if hourly_limit.is_some() {
+        "New Torrents"
+    } else {
+        "Subscription Digest"
+    };
+    message::add_system_message(&mut tx, &user.username, title, Some(&body)).await?;
+    tx.commit().await?;
+
+    Ok(matched.len() as u64)
+}
@@ -1,13 +1,13 @@
 use super::*;
 
-pub async fn update_or_add_tag(client: &sqlx::PgPool, name: &str) -> Result<(), Error> {
+pub async fn update_or_add_tag(tx: &mut sqlx::PgConnection, name: &str) -> Result<(), Error> {
     sqlx::query!(
         "INSERT INTO tag(name) VALUES($1) \
         ON CONFLICT (name) DO \
         UPDATE SET amount = tag.amount + 1;",
         name
     )
-    .execute(client)
+    .execute(&mut *tx)
     .await?;
 
     Ok(())
@@ -0,0 +1,143 @@
+use super::*;
+use std::convert::TryFrom;
+
+/// kinds of tokens kept in `user_token`, applied to a torrent for a while
+/// 1. Freeleech, download is not counted
+/// 2. DoubleUpload, upload is counted twice
+#[repr(C)]
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum TokenKind {
+    Freeleech = 0,
+    DoubleUpload,
+}
+
+impl TryFrom<i32> for TokenKind {
+    type Error = Error;
+
+    fn try_from(v: i32) -> Result<Self, Self::Error> {
+        match v {
+            x if x == TokenKind::Freeleech as i32 => Ok(TokenKind::Freeleech),
+            x if x == TokenKind::DoubleUpload as i32 => Ok(TokenKind::DoubleUpload),
+            _ => Err(Error::RequestError("unknown token kind".to_string())),
+        }
+    }
+}
+
+/// give tokens inside a transaction
+pub async fn add_tokens(
+    tx: &mut sqlx::PgConnection,
+    uid: i64,
+    kind: TokenKind,
+    amount: i32,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO user_token(uid, kind, amount) \
+        VALUES($1, $2, $3) ON CONFLICT (uid, kind) DO \
+        UPDATE SET amount = user_token.amount + $3;",
+        uid,
+        kind as i32,
+        amount
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_tokens_by_uid(client: &sqlx::PgPool, uid: i64) -> UserTokenVecRet {
+    Ok(sqlx::query_as!(
+        UserToken,
+        "SELECT kind, amount FROM user_token \
+        WHERE uid = $1 ORDER BY kind;",
+        uid
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// Consume a token and apply it to the torrent in one transaction.
+/// A token of the same kind cannot be applied to a torrent twice at the same time.
+pub async fn apply_token(
+    client: &sqlx::PgPool,
+    uid: i64,
+    tid: i64,
+    kind: TokenKind,
+    hours: i64,
+) -> TokenUsageRet {
+    let mut tx = client.begin().await?;
+    let ret = sqlx::query!(
+        "UPDATE user_token SET amount = amount - 1 \
+        WHERE uid = $1 AND kind = $2 AND amount > 0;",
+        uid,
+        kind as i32
+    )
+    .execute(&mut *tx)
+    .await?;
+    if ret.rows_affected() == 0 {
+        return Err(Error::RequestError("no token left".to_string()));
+    }
+    let active = sqlx::query!(
+        "SELECT COUNT(*) FROM token_usage \
+        WHERE uid = $1 AND tid = $2 AND kind = $3 AND endTime > now();",
+        uid,
+        tid,
+        kind as i32
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .count
+    .unwrap_or(0);
+    if active > 0 {
+        return Err(Error::RequestError("token already applied".to_string()));
+    }
+
+    let usage = sqlx::query_as!(
+        TokenUsage,
+        "WITH ret AS (\
+            INSERT INTO token_usage(uid, tid, kind, endTime) \
+            VALUES($1, $2, $3, now() + make_interval(hours => $4)) RETURNING *\
+        ) SELECT ret.id, ret.tid, torrent_info.title, ret.kind, ret.startTime, ret.endTime \
+        FROM ret INNER JOIN torrent_info ON ret.tid = torrent_info.id;",
+        uid,
+        tid,
+        kind as i32,
+        hours as i32
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(usage)
+}
+
+/// all tokens ever applied by the user, latest first
+pub async fn find_token_usages_by_uid(client: &sqlx::PgPool, uid: i64) -> TokenUsageVecRet {
+    Ok(sqlx::query_as!(
+        TokenUsage,
+        "SELECT token_usage.id, tid, torrent_info.title, kind, startTime, endTime \
+        FROM token_usage INNER JOIN torrent_info ON token_usage.tid = torrent_info.id \
+        WHERE uid = $1 ORDER BY token_usage.id DESC;",
+        uid
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+/// kinds of tokens in effect for the user on the torrent
+pub async fn find_active_tokens(
+    client: &sqlx::PgPool,
+    uid: i64,
+    tid: i64,
+) -> Result<Vec<TokenKind>, Error> {
+    sqlx::query!(
+        "SELECT DISTINCT kind FROM token_usage \
+        WHERE uid = $1 AND tid = $2 AND now() BETWEEN startTime AND endTime;",
+        uid,
+        tid
+    )
+    .fetch_all(client)
+    .await?
+    .into_iter()
+    .map(|r| TokenKind::try_from(r.kind))
+    .collect()
+}
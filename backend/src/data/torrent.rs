@@ -1,42 +1,169 @@
 use super::*;
 use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
 
 type TorrentRet = Result<TorrentTable, Error>;
 
+/// torrent to compare with when looking for duplicates
+#[derive(Debug)]
+pub struct DupeCandidate {
+    pub id: i64,
+    pub info: Vec<u8>,
+}
+
 /// a file struct used when parse torrent
 #[derive(Debug, Deserialize, Serialize)]
 pub struct File {
     pub path: Vec<String>,
     pub length: i64,
+    pub attr: Option<String>,
+}
+
+impl File {
+    /// padding files aligning v1 pieces of hybrid torrents (BEP 47)
+    pub fn is_padding(&self) -> bool {
+        self.attr.as_deref().is_some_and(|attr| attr.contains('p'))
+    }
+}
+
+/// a file of v2 file tree, `pieces_root` is absent for empty files
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FileAttr {
+    pub length: i64,
+    #[serde(rename = "pieces root")]
+    pub pieces_root: Option<ByteBuf>,
+}
+
+/// a node of v2 file tree, files are keyed by an empty string
+/// and directories are keyed by their names
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum FileTreeNode {
+    File {
+        #[serde(rename = "")]
+        attr: FileAttr,
+    },
+    Directory(BTreeMap<String, FileTreeNode>),
+}
+
+impl FileTreeNode {
+    /// flatten the tree into files with their paths
+    pub fn flatten<'a>(&'a self, path: Vec<String>, ret: &mut Vec<(Vec<String>, &'a FileAttr)>) {
+        match self {
+            FileTreeNode::File { attr } => ret.push((path, attr)),
+            FileTreeNode::Directory(children) => {
+                for (name, child) in children {
+                    let mut path = path.clone();
+                    path.push(name.clone());
+                    child.flatten(path, ret);
+                }
+            }
+        }
+    }
 }
 
 /// 1. name: name of torrent
-/// 2. pieces: hash pieces of file
+/// 2. pieces: hash pieces of file, absent in v2 only torrents
 /// 3. piece_length: how many pieces there are
 /// 4. length: total length of torrent
 /// 5. files: file list
 /// 6. private: whether torrent is private
 /// in our case it is always 1
+/// 7. meta_version: 2 for v2 and hybrid torrents
+/// 8. file_tree: v2 file list
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Info {
     pub name: String,
     // much more faster to deserialize
-    pub pieces: ByteBuf,
+    pub pieces: Option<ByteBuf>,
     #[serde(rename = "piece length")]
     pub piece_length: i64,
     pub length: Option<i64>,
     pub files: Option<Vec<File>>,
     pub private: Option<u8>,
+    #[serde(rename = "meta version")]
+    pub meta_version: Option<u8>,
+    #[serde(rename = "file tree")]
+    pub file_tree: Option<BTreeMap<String, FileTreeNode>>,
+}
+
+impl Info {
+    /// v1 and hybrid torrents
+    pub fn is_v1(&self) -> bool {
+        self.pieces.is_some()
+    }
+
+    /// v2 and hybrid torrents
+    pub fn is_v2(&self) -> bool {
+        self.meta_version == Some(2) && self.file_tree.is_some()
+    }
+
+    /// single file torrent, whose name is the file name
+    pub fn is_single_file(&self) -> bool {
+        match (&self.files, &self.file_tree) {
+            (Some(_), _) => false,
+            (None, Some(tree)) => {
+                tree.len() == 1 && matches!(tree.get(&self.name), Some(FileTreeNode::File { .. }))
+            }
+            (None, None) => true,
+        }
+    }
+
+    /// files in v2 file tree with their paths
+    pub fn tree_files(&self) -> Vec<(Vec<String>, &FileAttr)> {
+        let mut ret = vec![];
+        for (name, node) in self.file_tree.iter().flatten() {
+            node.flatten(vec![name.clone()], &mut ret);
+        }
+        ret
+    }
+
+    /// paths and lengths of files, padding files are skipped
+    pub fn file_list(&self) -> Vec<(Vec<String>, i64)> {
+        match (&self.files, &self.file_tree) {
+            (Some(files), _) => files
+                .iter()
+                .filter(|file| !file.is_padding())
+                .map(|file| (file.path.clone(), file.length))
+                .collect(),
+            (None, Some(_)) => self
+                .tree_files()
+                .into_iter()
+                .map(|(path, attr)| (path, attr.length))
+                .collect(),
+            (None, None) => vec![(vec![self.name.clone()], self.length.unwrap_or(0))],
+        }
+    }
+
+    /// total length of files, `None` on overflow
+    pub fn total_length(&self) -> Option<i64> {
+        self.file_list()
+            .iter()
+            .try_fold(0i64, |sum, (_, length)| sum.checked_add(*length))
+    }
+
+    /// length covered by v1 pieces, padding files included, `None` on overflow
+    pub fn v1_length(&self) -> Option<i64> {
+        match &self.files {
+            Some(files) => files
+                .iter()
+                .try_fold(0i64, |sum, file| sum.checked_add(file.length)),
+            None => Some(self.length.unwrap_or(0)),
+        }
+    }
 }
 
 /// 1. info: `Info` struct
 /// 2. announce: announce list, in our case it is generated
 /// 3. comment: comment by torrent maker
+/// 4. piece_layers: merkle layers of v2 files, keyed by pieces root
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Torrent {
     pub info: Info,
     pub announce: Option<String>,
     pub comment: Option<String>,
+    #[serde(rename = "piece layers")]
+    pub piece_layers: Option<BTreeMap<ByteBuf, ByteBuf>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -47,7 +174,14 @@ pub struct TorrentTable {
     pub comment: Option<String>,
     pub files: Vec<String>,
     pub info: Vec<u8>,
+    /// sha1 infohash, or truncated sha256 one for v2 only torrents
     pub infohash: String,
+    /// sha256 infohash of v2 and hybrid torrents
+    pub infohashv2: Option<String>,
+    /// bencoded piece layers of v2 and hybrid torrents
+    pub piecelayers: Option<Vec<u8>>,
+    /// sizes of `files` in the same order
+    pub filesizes: Vec<i64>,
 }
 
 pub async fn update_or_add_torrent(
@@ -56,17 +190,21 @@ pub async fn update_or_add_torrent(
     id: i64,
 ) -> Result<(), Error> {
     sqlx::query!(
-        "INSERT INTO torrent(id, name, length, comment, files, info, infohash) \
-        VALUES($1, $2, $3, $4, $5, $6, $7) \
+        "INSERT INTO torrent(id, name, length, comment, files, info, infohash, infohashV2, pieceLayers, \
+        fileSizes) VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
         ON CONFLICT (id) DO \
-        UPDATE SET name = $2, length = $3, comment = $4, files = $5, info = $6, infohash = $7;",
+        UPDATE SET name = $2, length = $3, comment = $4, files = $5, info = $6, infohash = $7, \
+        infohashV2 = $8, pieceLayers = $9, fileSizes = $10, legacyCopy = FALSE;",
         id,
         torrent.name,
         torrent.length,
         torrent.comment,
         &torrent.files,
         &torrent.info,
-        torrent.infohash
+        torrent.infohash,
+        torrent.infohashv2,
+        torrent.piecelayers,
+        &torrent.filesizes
     )
     .execute(client)
     .await?;
@@ -76,8 +214,8 @@ pub async fn update_or_add_torrent(
 pub async fn find_torrent_by_id(client: &sqlx::PgPool, id: i64) -> TorrentRet {
     sqlx::query_as!(
         TorrentTable,
-        "SELECT * FROM torrent \
-        WHERE id = $1;",
+        "SELECT id, name, length, comment, files, info, infohash, infohashV2, pieceLayers, fileSizes \
+        FROM torrent WHERE id = $1;",
         id
     )
     .fetch_all(client)
@@ -85,3 +223,59 @@ pub async fn find_torrent_by_id(client: &sqlx::PgPool, id: i64) -> TorrentRet {
     .pop()
     .ok_or(Error::NotFound)
 }
+
+/// Sizes of stored info dict and piece layers of torrents `ids`, by which
+/// sizes of torrent files are known without loading them.
+pub async fn find_stored_sizes_by_ids(
+    client: &sqlx::PgPool,
+    ids: &[i64],
+) -> Result<Vec<(i64, i64)>, Error> {
+    Ok(sqlx::query!(
+        "SELECT id, (octet_length(info) + COALESCE(octet_length(pieceLayers), 0))::BIGINT AS \"size!\" \
+        FROM torrent WHERE id = ANY($1);",
+        ids
+    )
+    .fetch_all(client)
+    .await?
+    .into_iter()
+    .map(|r| (r.id, r.size))
+    .collect())
+}
+
+/// find another torrent with the same infohash
+pub async fn find_id_by_infohash(
+    client: &sqlx::PgPool,
+    torrent: &TorrentTable,
+    except: i64,
+) -> Result<Option<i64>, Error> {
+    Ok(sqlx::query!(
+        "SELECT id FROM torrent \
+        WHERE (infohash = $1 OR infohashV2 = $2) AND id <> $3 LIMIT 1;",
+        torrent.infohash,
+        torrent.infohashv2,
+        except
+    )
+    .fetch_optional(client)
+    .await?
+    .map(|r| r.id))
+}
+
+/// torrents of length within 10 percent, closest first
+pub async fn find_dupe_candidates(
+    client: &sqlx::PgPool,
+    length: i64,
+    except: i64,
+) -> Result<Vec<DupeCandidate>, Error> {
+    Ok(sqlx::query_as!(
+        DupeCandidate,
+        "SELECT id, info FROM torrent \
+        WHERE length BETWEEN $1 AND $2 AND id <> $3 \
+        ORDER BY ABS(length - $4) LIMIT 100;",
+        length - length / 10,
+        length + length / 10,
+        except,
+        length
+    )
+    .fetch_all(client)
+    .await?)
+}
@@ -45,8 +45,8 @@ pub async fn update_torrent_info(
 pub async fn find_torrent_by_id_mini(client: &sqlx::PgPool, id: i64) -> MiniTorrentRet {
     sqlx::query_as!(
         MiniTorrent,
-        "SELECT poster, visible, free, tag, length \
-        FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
+        "SELECT torrent_info.id, title, poster, visible, promotion, promotionStart, promotionEnd, \
+        tag, length FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
         WHERE torrent_info.id = $1;",
         id
     )
@@ -56,15 +56,16 @@ pub async fn find_torrent_by_id_mini(client: &sqlx::PgPool, id: i64) -> MiniTorr
     .ok_or(Error::NotFound)
 }
 
-pub async fn make_torrent_visible(client: &sqlx::PgPool, ids: &[i64]) -> MiniTorrentVecRet {
+pub async fn make_torrent_visible(tx: &mut sqlx::PgConnection, ids: &[i64]) -> MiniTorrentVecRet {
     Ok(sqlx::query_as!(
         MiniTorrent,
         "UPDATE torrent_info SET visible = TRUE FROM torrent \
         WHERE torrent_info.id = torrent.id AND torrent_info.id = ANY($1) \
-        RETURNING poster, visible, free, tag, length;",
+        RETURNING torrent_info.id, title, poster, visible, promotion, promotionStart, promotionEnd, \
+        tag, length;",
         ids
     )
-    .fetch_all(client)
+    .fetch_all(&mut *tx)
     .await?)
 }
 
@@ -72,7 +73,7 @@ pub async fn find_stick_torrent(client: &sqlx::PgPool) -> SlimTorrentVecRet {
     Ok(sqlx::query_as!(
         SlimTorrent,
         "SELECT torrent_info.id, title, poster, tag, lastEdit, length,\
-        free, downloading, uploading, finished \
+        promotion, downloading, uploading, finished, promotionStart, promotionEnd \
         FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
         WHERE visible = TRUE AND stick = TRUE \
         ORDER BY lastEdit DESC;",
@@ -89,7 +90,7 @@ pub async fn find_visible_torrent_by_tag_desc(
 ) -> SlimTorrentVecRet {
     Ok(sqlx::query_as!(
         SlimTorrent,
-        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, free, downloading, uploading, finished \
+        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, promotion, downloading, uploading, finished, promotionStart, promotionEnd \
         FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
         WHERE visible = TRUE AND ($1::VARCHAR[] <@ tag) AND stick = FALSE \
         ORDER BY CASE \
@@ -118,7 +119,7 @@ pub async fn find_visible_torrent_by_tag_asc(
 ) -> SlimTorrentVecRet {
     Ok(sqlx::query_as!(
         SlimTorrent,
-        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, free, downloading, uploading, finished \
+        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, promotion, downloading, uploading, finished, promotionStart, promotionEnd \
         FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
         WHERE visible = TRUE AND ($1::VARCHAR[] <@ tag) AND stick = FALSE \
         ORDER BY CASE \
@@ -147,7 +148,7 @@ pub async fn find_visible_torrent_by_ids_desc(
 ) -> SlimTorrentVecRet {
     Ok(sqlx::query_as!(
         SlimTorrent,
-        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, free, downloading, uploading, finished \
+        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, promotion, downloading, uploading, finished, promotionStart, promotionEnd \
         FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
         WHERE visible = TRUE AND torrent_info.id = ANY($1) \
         ORDER BY CASE \
@@ -176,7 +177,7 @@ pub async fn find_visible_torrent_by_ids_asc(
 ) -> SlimTorrentVecRet {
     Ok(sqlx::query_as!(
         SlimTorrent,
-        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, free, downloading, uploading, finished \
+        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, promotion, downloading, uploading, finished, promotionStart, promotionEnd \
         FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
         WHERE visible = TRUE AND torrent_info.id = ANY($1) \
         ORDER BY CASE \
@@ -197,11 +198,39 @@ pub async fn find_visible_torrent_by_ids_asc(
         .await?)
 }
 
-pub async fn find_invisible_torrent(client: &sqlx::PgPool) -> SlimTorrentVecRet {
+/// Latest visible torrents for feeds. Torrents have all of `tags`,
+/// are in `ids` unless it is none, and have length within the bounds.
+pub async fn find_feed_torrents(
+    client: &sqlx::PgPool,
+    tags: &[String],
+    ids: Option<&[i64]>,
+    min_length: Option<i64>,
+    max_length: Option<i64>,
+    limit: i64,
+) -> FeedTorrentVecRet {
     Ok(sqlx::query_as!(
-        SlimTorrent,
-        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, \
-        free, downloading, uploading, finished \
+        FeedTorrent,
+        "SELECT torrent_info.id, title, poster, description, tag, lastEdit, length, \
+        promotion, promotionStart, promotionEnd \
+        FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
+        WHERE visible = TRUE AND (CARDINALITY($1::VARCHAR[]) = 0 OR $1 <@ tag) \
+        AND ($2::BIGINT[] IS NULL OR torrent_info.id = ANY($2)) \
+        AND ($3::BIGINT IS NULL OR length >= $3) AND ($4::BIGINT IS NULL OR length <= $4) \
+        ORDER BY lastEdit DESC LIMIT $5;",
+        tags,
+        ids,
+        min_length,
+        max_length,
+        limit
+    )
+    .fetch_all(client)
+    .await?)
+}
+
+pub async fn find_invisible_torrent(client: &sqlx::PgPool) -> ReviewTorrentVecRet {
+    Ok(sqlx::query_as!(
+        ReviewTorrent,
+        "SELECT torrent_info.id, title, poster, tag, lastEdit, length, dupeOf, dupeScore \
         FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
         WHERE visible = FALSE;"
     )
@@ -209,11 +238,29 @@ pub async fn find_invisible_torrent(client: &sqlx::PgPool) -> SlimTorrentVecRet
     .await?)
 }
 
+/// flag or unflag a torrent as probable duplicate of another one
+pub async fn update_dupe_by_id(
+    client: &sqlx::PgPool,
+    id: i64,
+    dupe: Option<(i64, f64)>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE torrent_info SET dupeOf = $1, dupeScore = $2 \
+        WHERE id = $3;",
+        dupe.map(|(dupe_of, _)| dupe_of),
+        dupe.map(|(_, score)| score),
+        id
+    )
+    .execute(client)
+    .await?;
+    Ok(())
+}
+
 pub async fn find_torrent_by_poster(client: &sqlx::PgPool, poster: &str) -> SlimTorrentVecRet {
     Ok(sqlx::query_as!(
         SlimTorrent,
         "SELECT torrent_info.id, title, poster, tag, lastEdit, \
-        length, free, downloading, uploading, finished \
+        length, promotion, downloading, uploading, finished, promotionStart, promotionEnd \
         FROM torrent_info INNER JOIN torrent ON torrent_info.id = torrent.id \
         WHERE poster = $1",
         poster
@@ -226,8 +273,8 @@ pub async fn find_torrent_by_id(client: &sqlx::PgPool, id: i64) -> FullTorrentRe
     // left join cannot check
     sqlx::query_as_unchecked!(
         FullTorrent,
-        "SELECT torrent_info.id, title, poster, description, tag, visible, createTime, lastEdit, free, downloading, \
-        uploading, finished, length, files, infohash \
+        "SELECT torrent_info.id, title, poster, description, tag, visible, createTime, lastEdit, promotion, downloading, \
+        uploading, finished, promotionStart, promotionEnd, length, files, infohash \
         FROM torrent_info LEFT JOIN torrent ON torrent_info.id = torrent.id \
         WHERE torrent_info.id = $1;",
         id
@@ -274,30 +321,6 @@ pub async fn make_torrent_unstick(client: &sqlx::PgPool, ids: &[i64]) -> Result<
     Ok(())
 }
 
-pub async fn make_torrent_free(client: &sqlx::PgPool, ids: &[i64]) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE torrent_info SET free = TRUE \
-        WHERE id = ANY($1);",
-        ids
-    )
-    .execute(client)
-    .await?;
-
-    Ok(())
-}
-
-pub async fn make_torrent_unfree(client: &sqlx::PgPool, ids: &[i64]) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE torrent_info SET free = FALSE \
-        WHERE id = ANY($1);",
-        ids
-    )
-    .execute(client)
-    .await?;
-
-    Ok(())
-}
-
 pub async fn update_torrent_status(
     client: &sqlx::PgPool,
     id: i64,
@@ -1,5 +1,9 @@
 use super::*;
 
+/// at most 2 announce intervals are counted between two announces,
+/// in case the client went away without a stop.
+const MAX_SEED_INTERVAL: i64 = 3600;
+
 pub async fn find_status_by_tid_uid(
     client: &sqlx::PgPool,
     tid: i64,
@@ -19,7 +23,8 @@ pub async fn find_status_by_tid_uid(
 pub async fn find_downloading_torrent(client: &sqlx::PgPool, uid: i64) -> PersonalTorrentVecRet {
     Ok(sqlx::query_as!(
         PersonalTorrent,
-        "SELECT torrent_info.id, title, length, torrent_status.upload, torrent_status.download, torrent_info.free \
+        "SELECT torrent_info.id, title, length, torrent_status.upload, torrent_status.download, torrent_info.promotion, \
+        torrent_info.promotionStart, torrent_info.promotionEnd \
         FROM torrent_status INNER JOIN torrent ON torrent_status.tid = torrent.id INNER JOIN torrent_info ON \
         torrent.id = torrent_info.id \
         WHERE status = 0 AND uid = $1;",
@@ -32,7 +37,8 @@ pub async fn find_downloading_torrent(client: &sqlx::PgPool, uid: i64) -> Person
 pub async fn find_uploading_torrent(client: &sqlx::PgPool, uid: i64) -> PersonalTorrentVecRet {
     Ok(sqlx::query_as!(
         PersonalTorrent,
-        "SELECT torrent_info.id, title, length, torrent_status.upload, torrent_status.download, torrent_info.free \
+        "SELECT torrent_info.id, title, length, torrent_status.upload, torrent_status.download, torrent_info.promotion, \
+        torrent_info.promotionStart, torrent_info.promotionEnd \
         FROM torrent_status INNER JOIN torrent ON torrent_status.tid = torrent.id INNER JOIN torrent_info ON \
         torrent.id = torrent_info.id \
         WHERE status = 1 AND uid = $1;",
@@ -45,7 +51,8 @@ pub async fn find_uploading_torrent(client: &sqlx::PgPool, uid: i64) -> Personal
 pub async fn find_finished_torrent(client: &sqlx::PgPool, uid: i64) -> PersonalTorrentVecRet {
     Ok(sqlx::query_as!(
         PersonalTorrent,
-        "SELECT torrent_info.id, title, length, torrent_status.upload, torrent_status.download, torrent_info.free \
+        "SELECT torrent_info.id, title, length, torrent_status.upload, torrent_status.download, torrent_info.promotion, \
+        torrent_info.promotionStart, torrent_info.promotionEnd \
         FROM torrent_status INNER JOIN torrent ON torrent_status.tid = torrent.id INNER JOIN torrent_info ON \
         torrent.id = torrent_info.id \
         WHERE torrent_status.finished = TRUE AND uid = $1;",
@@ -58,7 +65,8 @@ pub async fn find_finished_torrent(client: &sqlx::PgPool, uid: i64) -> PersonalT
 pub async fn find_unfinished_torrent(client: &sqlx::PgPool, uid: i64) -> PersonalTorrentVecRet {
     Ok(sqlx::query_as!(
         PersonalTorrent,
-        "SELECT torrent_info.id, title, length, torrent_status.upload, torrent_status.download, torrent_info.free \
+        "SELECT torrent_info.id, title, length, torrent_status.upload, torrent_status.download, torrent_info.promotion, \
+        torrent_info.promotionStart, torrent_info.promotionEnd \
         FROM torrent_status INNER JOIN torrent ON torrent_status.tid = torrent.id INNER JOIN torrent_info ON \
         torrent.id = torrent_info.id \
         WHERE torrent_status.finished = FALSE AND status = 2 AND uid = $1;",
@@ -68,6 +76,27 @@ pub async fn find_unfinished_torrent(client: &sqlx::PgPool, uid: i64) -> Persona
         .await?)
 }
 
+/// Seconds of the interval since last announce credited as seeding time.
+/// The interval is judged by the state reported at its start, so the
+/// leeching before a completing announce is not counted.
+fn seedtime_credit(was_seeding: bool, was_stopped: bool, elapsed: i64) -> i64 {
+    if was_seeding && !was_stopped {
+        elapsed.clamp(0, MAX_SEED_INTERVAL)
+    } else {
+        0
+    }
+}
+
+/// Update status and totals of an announce.
+///
+/// Time since last announce is counted as seeding time when peer was
+/// seeding and not stopped then. When `seeding` is unknown, a finished
+/// peer is taken as seeding.
+///
+/// `time` is when the tracker received the announce in unix seconds, so that
+/// a notification delivered late is credited as of then. A late one older
+/// than the last announce is credited nothing.
+#[allow(clippy::too_many_arguments)]
 pub async fn update_or_add_status(
     client: &sqlx::PgPool,
     tid: i64,
@@ -75,19 +104,43 @@ pub async fn update_or_add_status(
     status: i32,
     upload: i64,
     download: i64,
+    seeding: Option<bool>,
+    time: Option<i64>,
 ) -> Result<(), Error> {
+    let mut tx = client.begin().await?;
+    let credit = sqlx::query!(
+        "SELECT status, seeding, EXTRACT(EPOCH FROM \
+        LEAST(COALESCE(to_timestamp($3::BIGINT), now()), now()) - lastannounce)::BIGINT AS \"elapsed!\" \
+        FROM torrent_status WHERE tid = $1 AND uid = $2 FOR UPDATE;",
+        tid,
+        uid,
+        time
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .map_or(0, |prev| {
+        seedtime_credit(prev.seeding, prev.status == 2, prev.elapsed)
+    });
     sqlx::query!(
-        "INSERT INTO torrent_status(tid, uid, status, upload, download) \
-        VALUES($1, $2, $3, $4, $5) ON CONFLICT (tid, uid) DO \
-        UPDATE SET status = $3, upload = torrent_status.upload + $4, download = torrent_status.download + $5;",
+        "INSERT INTO torrent_status(tid, uid, status, upload, download, seeding, lastannounce) \
+        VALUES($1, $2, $3, $4, $5, COALESCE($6, false), \
+        LEAST(COALESCE(to_timestamp($8::BIGINT), now()), now())) ON CONFLICT (tid, uid) DO \
+        UPDATE SET status = $3, upload = torrent_status.upload + $4, download = torrent_status.download + $5, \
+        seedtime = torrent_status.seedtime + $7, \
+        seeding = COALESCE($6, torrent_status.finished), \
+        lastannounce = GREATEST(torrent_status.lastannounce, EXCLUDED.lastannounce);",
         tid,
         uid,
         status,
         upload,
-        download
+        download,
+        seeding,
+        credit,
+        time
         )
-        .execute(client)
+        .execute(&mut *tx)
         .await?;
+    tx.commit().await?;
 
     Ok(())
 }
@@ -98,7 +151,7 @@ pub async fn update_finished_by_tid_uid(
     uid: i64,
 ) -> Result<(), Error> {
     sqlx::query!(
-        "UPDATE torrent_status SET finished = TRUE \
+        "UPDATE torrent_status SET finished = TRUE, seeding = TRUE, finishtime = COALESCE(finishtime, now()) \
         WHERE tid = $1 AND uid = $2;",
         tid,
         uid
@@ -108,3 +161,18 @@ pub async fn update_finished_by_tid_uid(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seedtime_credit_works() {
+        assert_eq!(seedtime_credit(true, false, 600), 600);
+        // completing announce ends a leeching interval
+        assert_eq!(seedtime_credit(false, false, 600), 0);
+        assert_eq!(seedtime_credit(true, true, 600), 0);
+        assert_eq!(seedtime_credit(true, false, 86400), MAX_SEED_INTERVAL);
+        assert_eq!(seedtime_credit(true, false, -5), 0);
+    }
+}
@@ -65,6 +65,32 @@ pub async fn find_user_by_email(client: &sqlx::PgPool, email: &str) -> AccountRe
     .ok_or(Error::NotFound)
 }
 
+pub async fn find_user_by_passkey(client: &sqlx::PgPool, passkey: &str) -> AccountRet {
+    sqlx::query_as!(
+        Account,
+        "SELECT id, email, username, passkey, role FROM users \
+        WHERE passkey = $1;",
+        passkey
+    )
+    .fetch_all(client)
+    .await?
+    .pop()
+    .ok_or(Error::NotFound)
+}
+
+pub async fn find_user_by_feed_token(client: &sqlx::PgPool, token: &str) -> AccountRet {
+    sqlx::query_as!(
+        Account,
+        "SELECT id, email, username, passkey, role FROM users \
+        WHERE feedToken = $1;",
+        token
+    )
+    .fetch_all(client)
+    .await?
+    .pop()
+    .ok_or(Error::NotFound)
+}
+
 pub async fn list_banned_user(client: &sqlx::PgPool) -> AccountVecRet {
     Ok(sqlx::query_as!(
         Account,
@@ -145,6 +171,23 @@ pub async fn update_passkey_by_username(
     Ok(())
 }
 
+pub async fn update_feed_token_by_username(
+    client: &sqlx::PgPool,
+    username: &str,
+    new_token: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE users SET feedToken = $1 \
+         WHERE username = $2;",
+        new_token,
+        username
+    )
+    .execute(client)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn add_role_by_id(client: &sqlx::PgPool, id: i64, bit: i32) -> Result<(), Error> {
     sqlx::query!(
         "UPDATE users SET role = role | (1::BIGINT << $1) \
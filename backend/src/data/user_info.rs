@@ -63,7 +63,7 @@ pub async fn find_user_info_by_name(client: &sqlx::PgPool, username: &str) -> Us
             SELECT user_info.id, rank.name FROM rank INNER JOIN user_info ON rank.id = user_info.rank
             WHERE user_info.username = $1
         ) SELECT users.id, users.username, registerTime, lastActivity, invitor, upload, download, user_info.money, \
-        ret.name as rank, avatar, other, privacy, email, passkey FROM user_info INNER JOIN users ON user_info.id = users.id \
+        ret.name as rank, avatar, other, privacy, email, passkey, invites, title FROM user_info INNER JOIN users ON user_info.id = users.id \
         INNER JOIN ret ON user_info.id = ret.id WHERE user_info.username = $1",
         username
         )
@@ -104,73 +104,20 @@ pub async fn add_invitor_by_name(
     Ok(())
 }
 
-pub async fn transfer_money_by_name(
-    client: &sqlx::PgPool,
-    from: &str,
-    to: &str,
-    amount: f64,
-) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE user_info SET money = CASE \
-            WHEN username = $1 THEN money - $3 \
-            WHEN username = $2 THEN money + $3 \
-        END \
-        WHERE username in ($1, $2)",
-        from,
-        to,
-        amount
-    )
-    .execute(client)
-    .await?;
-
-    Ok(())
-}
-
-pub async fn award_money_by_id(
-    client: &sqlx::PgPool,
-    ids: &[i64],
-    amount: f64,
-) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE user_info SET money = money + $1 \
-        WHERE id = ANY($2);",
-        amount,
-        ids
-    )
-    .execute(client)
-    .await?;
-
-    Ok(())
-}
-
-pub async fn update_money_by_name(
-    client: &sqlx::PgPool,
+/// take an invitation slot if there is any
+pub async fn consume_invite_by_name(
+    tx: &mut sqlx::PgConnection,
     username: &str,
-    amount: f64,
-) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE user_info SET money = money + $1 \
-        WHERE username = $2;",
-        amount,
+) -> Result<bool, Error> {
+    let ret = sqlx::query!(
+        "UPDATE user_info SET invites = invites - 1 \
+        WHERE username = $1 AND invites > 0;",
         username
     )
-    .execute(client)
+    .execute(&mut *tx)
     .await?;
 
-    Ok(())
-}
-
-pub async fn update_money_by_id(client: &sqlx::PgPool, id: i64, amount: f64) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE user_info SET money = money + $1 \
-        WHERE id = $2;",
-        amount,
-        id
-    )
-    .execute(client)
-    .await?;
-
-    Ok(())
+    Ok(ret.rows_affected() > 0)
 }
 
 /// update user define columns, replace all without any check
@@ -224,33 +171,3 @@ pub async fn update_privacy_by_name(
 
     Ok(())
 }
-
-pub async fn update_rank_by_name(
-    client: &sqlx::PgPool,
-    username: &str,
-    rank: i32,
-) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE user_info SET rank = $1 \
-        WHERE username = $2;",
-        rank,
-        username
-    )
-    .execute(client)
-    .await?;
-
-    Ok(())
-}
-
-pub async fn update_rank_by_id(client: &sqlx::PgPool, id: i64, rank: i32) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE user_info SET rank = $1 \
-        WHERE id = $2;",
-        rank,
-        id
-    )
-    .execute(client)
-    .await?;
-
-    Ok(())
-}
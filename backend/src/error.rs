@@ -14,6 +14,7 @@ use std::fmt::{Display, Formatter};
 /// 6. Request Error
 /// 7. KVError, wrapper for persistent KV
 /// 8. OSSError, wrapper for OSS error
+/// 9. InvalidTorrent, with every violation of uploaded torrent
 ///
 /// All errors will be transformed to Http Response so no panic will happen.
 #[derive(Debug)]
@@ -26,6 +27,7 @@ pub enum Error {
     OSSError(Box<dyn std::error::Error + Send + Sync>),
     NoPermission,
     RequestError(String),
+    InvalidTorrent(Vec<String>),
 }
 
 impl From<sqlx::Error> for Error {
@@ -98,6 +100,13 @@ impl ResponseError for Error {
             Error::RequestError(ref err) => {
                 HttpResponse::UnprocessableEntity().json(GeneralResponse::from_err(err))
             }
+            Error::InvalidTorrent(ref violations) => {
+                HttpResponse::UnprocessableEntity().json(GeneralResponse {
+                    data: serde_json::json!(violations),
+                    success: false,
+                    err_msg: format!("invalid torrent: {}", violations.join("; ")),
+                })
+            }
         }
     }
 }
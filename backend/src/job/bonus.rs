@@ -0,0 +1,156 @@
+use crate::data::bonus as bonus_model;
+use crate::data::kv::KVDB;
+use crate::data::SeedingTorrent;
+use crate::error::Error;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_2_PI, SQRT_2};
+
+const GIB: f64 = (1_u64 << 30) as f64;
+const WEEK: f64 = 7.0 * 24.0 * 3600.0;
+
+/// Bonus points per hour, as NexusPHP does.
+///
+/// Each seeding torrent gets a weight
+/// `(1 - 10 ^ (-T / T0)) * S * (1 + √2 * 10 ^ (-(N - 1) / (N0 - 1)))`,
+/// where T is its age in weeks, S its size in GiB and N the number of seeders.
+/// Older, larger and less seeded torrents weigh more.
+///
+/// And then points of an hour is `B0 * 2 / π * arctan(A / L)`,
+/// where A is the sum of weights. So it never exceeds B0.
+#[derive(Debug, Clone, Copy)]
+pub struct BonusFormula {
+    pub t0: f64,
+    pub n0: f64,
+    pub b0: f64,
+    pub l: f64,
+}
+
+impl BonusFormula {
+    pub fn from_settings() -> Result<Self, Error> {
+        let get = |key: &str| -> Result<f64, Error> {
+            Ok(KVDB.clone().get_float("config", key.as_ref())?.unwrap())
+        };
+        Ok(BonusFormula {
+            t0: get("BONUS T0")?,
+            n0: get("BONUS N0")?,
+            b0: get("BONUS B0")?,
+            l: get("BONUS L")?,
+        })
+    }
+
+    /// weight of a single seeding torrent
+    pub fn weight(&self, weeks: f64, size: f64, seeders: i64) -> f64 {
+        let weeks = weeks.max(0.0);
+        let seeders = seeders.max(1) as f64;
+        let age_factor = 1.0 - 10_f64.powf(-weeks / self.t0);
+        let seeder_factor = 1.0 + SQRT_2 * 10_f64.powf(-(seeders - 1.0) / (self.n0 - 1.0).max(1.0));
+        age_factor * size * seeder_factor
+    }
+
+    /// points for an hour of seeding with total weight
+    pub fn hourly(&self, weight: f64) -> f64 {
+        if weight <= 0.0 {
+            return 0.0;
+        }
+        self.b0 * FRAC_2_PI * (weight / self.l).atan()
+    }
+
+    pub fn torrent_weight(&self, torrent: &SeedingTorrent, now: DateTime<Utc>) -> f64 {
+        let weeks = (now - torrent.createtime).num_seconds() as f64 / WEEK;
+        let size = torrent.length as f64 / GIB;
+        self.weight(weeks, size, torrent.seeders.unwrap_or(1))
+    }
+}
+
+/// Award every user seeding in the last hour.
+pub async fn run(client: sqlx::PgPool) -> Result<(), Error> {
+    let formula = BonusFormula::from_settings()?;
+    let now = Utc::now();
+    let torrents =
+        bonus_model::find_seeding_torrents(&client, None, now - Duration::hours(1)).await?;
+
+    let mut weights: HashMap<i64, (i32, f64)> = HashMap::new();
+    for torrent in torrents.iter() {
+        let entry = weights.entry(torrent.uid).or_default();
+        entry.0 += 1;
+        entry.1 += formula.torrent_weight(torrent, now);
+    }
+    let awards: Vec<(i64, f64, i32, f64)> = weights
+        .into_iter()
+        .map(|(uid, (count, weight))| (uid, formula.hourly(weight), count, weight))
+        .filter(|(_, amount, _, _)| *amount > 0.0)
+        .collect();
+    // a little less than an hour, as the job is not run exactly every hour
+    let period = Duration::minutes(55).num_seconds() as f64;
+    if bonus_model::award_bonuses(&client, &awards, period).await? {
+        log::info!("bonus: {} users awarded", awards.len());
+    } else {
+        log::warn!("bonus: awarded within the hour already, skipped");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMULA: BonusFormula = BonusFormula {
+        t0: 4.0,
+        n0: 7.0,
+        b0: 100.0,
+        l: 300.0,
+    };
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn test_weight() {
+        // brand new torrent weighs nothing
+        assert!(approx(FORMULA.weight(0.0, 10.0, 1), 0.0));
+        // T = T0, N = 1: 0.9 * S * (1 + √2)
+        assert!(approx(FORMULA.weight(4.0, 10.0, 1), 9.0 * (1.0 + SQRT_2)));
+        // N = N0: seeder factor becomes 1 + √2 / 10
+        assert!(approx(
+            FORMULA.weight(4.0, 10.0, 7),
+            9.0 * (1.0 + SQRT_2 / 10.0)
+        ));
+        // no seeders is taken as 1
+        assert!(approx(
+            FORMULA.weight(4.0, 10.0, 0),
+            FORMULA.weight(4.0, 10.0, 1)
+        ));
+        // future torrent makes no sense
+        assert!(approx(FORMULA.weight(-1.0, 10.0, 1), 0.0));
+    }
+
+    #[test]
+    fn test_weight_monotonic() {
+        assert!(FORMULA.weight(8.0, 10.0, 3) > FORMULA.weight(4.0, 10.0, 3));
+        assert!(FORMULA.weight(4.0, 20.0, 3) > FORMULA.weight(4.0, 10.0, 3));
+        assert!(FORMULA.weight(4.0, 10.0, 3) > FORMULA.weight(4.0, 10.0, 30));
+    }
+
+    #[test]
+    fn test_hourly() {
+        assert!(approx(FORMULA.hourly(0.0), 0.0));
+        // A = L: B0 * 2 / π * π / 4
+        assert!(approx(FORMULA.hourly(300.0), 50.0));
+        assert!(FORMULA.hourly(1e12) < 100.0);
+        assert!(FORMULA.hourly(600.0) > FORMULA.hourly(300.0));
+    }
+
+    #[test]
+    fn test_degenerated_settings() {
+        let formula = BonusFormula {
+            n0: 1.0,
+            l: 0.0,
+            ..FORMULA
+        };
+        assert!(formula.weight(4.0, 10.0, 2).is_finite());
+        assert!(approx(formula.hourly(1.0), 100.0));
+    }
+}
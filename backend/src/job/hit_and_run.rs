@@ -0,0 +1,39 @@
+use crate::data::hit_and_run as hit_and_run_model;
+use crate::data::hit_and_run::Rule;
+use crate::data::kv::KVDB;
+use crate::error::Error;
+use chrono::Utc;
+
+/// Clear records made up for, and mark new hit and runs.
+/// Rules of torrents override the site settings.
+pub async fn run(client: sqlx::PgPool) -> Result<(), Error> {
+    let hours = KVDB
+        .clone()
+        .get_number("config", "HNR MIN SEED HOURS".as_ref())?
+        .unwrap();
+    let ratio = KVDB
+        .clone()
+        .get_float("config", "HNR MIN RATIO".as_ref())?
+        .unwrap();
+    let days = KVDB
+        .clone()
+        .get_number("config", "HNR GRACE DAYS".as_ref())?
+        .unwrap();
+    let default = Rule {
+        min_seedtime: hours * 3600,
+        min_ratio: ratio,
+        grace_days: days,
+    };
+    let rules: Vec<(i64, Rule)> = hit_and_run_model::list_hit_and_run_rules(&client)
+        .await?
+        .iter()
+        .map(|rule| (rule.tid, default.overridden_by(rule)))
+        .collect();
+
+    let cleared = hit_and_run_model::clear_hit_and_runs(&client, &default, &rules).await?;
+    let marked =
+        hit_and_run_model::mark_hit_and_runs(&client, Utc::now(), &default, &rules).await?;
+    log::info!("hit and run: {} marked, {} cleared", marked, cleared);
+
+    Ok(())
+}
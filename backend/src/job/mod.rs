@@ -0,0 +1,52 @@
+//! Periodic jobs running along with the server.
+//!
+//! Every job is a `async fn(sqlx::PgPool) -> Result<(), Error>`,
+//! errors are logged and the job will be retried in next period.
+//! The first run comes after a full period, so that a restart never
+//! runs a job twice in one period. Bonus also records its runs in
+//! `job_run` along with the awards, so that neither a restart nor
+//! another instance pays it twice an hour.
+
+pub mod bonus;
+mod hit_and_run;
+mod promotion;
+mod rank;
+mod ratio_watch;
+mod stat;
+mod subscription;
+
+use crate::error::Error;
+use actix_web::rt::time::{interval_at, Instant};
+use std::future::Future;
+use std::time::Duration;
+
+const MINUTE: Duration = Duration::from_secs(60);
+const HOUR: Duration = Duration::from_secs(3600);
+
+fn spawn_job<F, Fut>(name: &'static str, period: Duration, client: sqlx::PgPool, job: F)
+where
+    F: Fn(sqlx::PgPool) -> Fut + 'static,
+    Fut: Future<Output = Result<(), Error>>,
+{
+    actix_web::rt::spawn(async move {
+        let start = Instant::now() + period;
+        let mut interval = interval_at(start, period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = job(client.clone()).await {
+                log::error!("job {} failed: {:?}", name, e);
+            }
+        }
+    });
+}
+
+/// start all jobs, must be called inside actix runtime
+pub fn start_jobs(client: &sqlx::PgPool) {
+    spawn_job("hit and run", HOUR, client.clone(), hit_and_run::run);
+    spawn_job("bonus", HOUR, client.clone(), bonus::run);
+    spawn_job("promotion", MINUTE, client.clone(), promotion::run);
+    spawn_job("rank", HOUR, client.clone(), rank::run);
+    spawn_job("ratio watch", HOUR, client.clone(), ratio_watch::run);
+    spawn_job("stat", HOUR, client.clone(), stat::run);
+    spawn_job("subscription", HOUR, client.clone(), subscription::run);
+}
@@ -0,0 +1,12 @@
+use crate::data::promotion as promotion_model;
+use crate::error::Error;
+
+/// Remove promotions which have ended.
+pub async fn run(client: sqlx::PgPool) -> Result<(), Error> {
+    let expired = promotion_model::expire_promotions(&client).await?;
+    if expired > 0 {
+        log::info!("promotion: {} expired", expired);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use crate::data::rank as rank_model;
+use crate::data::{Rank, RankCandidate};
+use crate::error::Error;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// whether the user meets requirements of the rank
+fn meets(rank: &Rank, user: &RankCandidate, now: DateTime<Utc>) -> bool {
+    let ratio = if user.download == 0 {
+        f64::INFINITY
+    } else {
+        user.upload as f64 / user.download as f64
+    };
+    user.upload >= rank.upload
+        && (now - user.registertime).num_seconds() >= rank.age
+        && ratio >= rank.ratio
+        && user.seedtime >= rank.seedtime
+}
+
+/// Walk the rank chain from the current rank of the user.
+///
+/// The user is demoted to previous ranks until the requirements are met,
+/// then promoted along `next` as long as the requirements are met.
+/// Returns the rank the user deserves.
+fn evaluate(ranks: &HashMap<i32, Rank>, user: &RankCandidate, now: DateTime<Utc>) -> i32 {
+    let previous: HashMap<i32, i32> = ranks
+        .values()
+        .filter_map(|r| r.next.map(|next| (next, r.id)))
+        .collect();
+    let mut current = user.rank;
+    // a broken chain might have cycles
+    for _ in 0..ranks.len() {
+        match (ranks.get(&current), previous.get(&current)) {
+            (Some(rank), Some(prev)) if !meets(rank, user, now) => current = *prev,
+            _ => break,
+        }
+    }
+    for _ in 0..ranks.len() {
+        match ranks
+            .get(&current)
+            .and_then(|r| r.next)
+            .and_then(|next| ranks.get(&next))
+        {
+            Some(next) if meets(next, user, now) => current = next.id,
+            _ => break,
+        }
+    }
+    current
+}
+
+/// Promote or demote users by their upload, age, ratio and seeding time.
+pub async fn run(client: sqlx::PgPool) -> Result<(), Error> {
+    let ranks: HashMap<i32, Rank> = rank_model::find_all_ranks(&client)
+        .await?
+        .into_iter()
+        .map(|r| (r.id, r))
+        .collect();
+    let now = Utc::now();
+    let mut changed = 0;
+    for user in rank_model::find_rank_candidates(&client).await? {
+        let target = evaluate(&ranks, &user, now);
+        if target == user.rank {
+            continue;
+        }
+        let (from, to) = match (ranks.get(&user.rank), ranks.get(&target)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        let reason = if previous_of(&ranks, from.id, to.id) {
+            "requirements of current rank not met"
+        } else {
+            "requirements of next rank met"
+        };
+        rank_model::change_rank(&client, user.id, from, to, reason, false).await?;
+        changed += 1;
+    }
+    log::info!("rank: {} users changed", changed);
+
+    Ok(())
+}
+
+/// whether `to` is before `from` in the chain
+fn previous_of(ranks: &HashMap<i32, Rank>, from: i32, to: i32) -> bool {
+    let mut current = to;
+    for _ in 0..ranks.len() {
+        match ranks.get(&current).and_then(|r| r.next) {
+            Some(next) if next == from => return true,
+            Some(next) => current = next,
+            None => break,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn rank(id: i32, upload: i64, ratio: f64, next: Option<i32>) -> Rank {
+        Rank {
+            id,
+            name: format!("rank{}", id),
+            role: vec![0],
+            upload,
+            age: 0,
+            next,
+            ratio,
+            seedtime: 0,
+        }
+    }
+
+    fn chain() -> HashMap<i32, Rank> {
+        vec![
+            rank(1, 0, 0.0, Some(2)),
+            rank(2, 100, 1.0, Some(3)),
+            rank(3, 1000, 2.0, None),
+        ]
+        .into_iter()
+        .map(|r| (r.id, r))
+        .collect()
+    }
+
+    fn user(rank: i32, upload: i64, download: i64) -> RankCandidate {
+        RankCandidate {
+            id: 1,
+            rank,
+            upload,
+            download,
+            registertime: Utc::now() - Duration::days(1),
+            seedtime: 0,
+        }
+    }
+
+    #[test]
+    fn promote_along_chain_works() {
+        let ranks = chain();
+        let now = Utc::now();
+        assert_eq!(evaluate(&ranks, &user(1, 50, 0), now), 1);
+        assert_eq!(evaluate(&ranks, &user(1, 500, 100), now), 2);
+        assert_eq!(evaluate(&ranks, &user(1, 5000, 100), now), 3);
+    }
+
+    #[test]
+    fn demote_works() {
+        let ranks = chain();
+        let now = Utc::now();
+        // ratio falls below rank 3 but meets rank 2
+        assert_eq!(evaluate(&ranks, &user(3, 1500, 1000), now), 2);
+        // meets nothing above the first rank
+        assert_eq!(evaluate(&ranks, &user(3, 1500, 10000), now), 1);
+        assert!(previous_of(&ranks, 3, 1));
+        assert!(!previous_of(&ranks, 1, 3));
+    }
+
+    #[test]
+    fn broken_chain_terminates() {
+        let mut ranks = chain();
+        ranks.get_mut(&3).unwrap().next = Some(1);
+        let now = Utc::now();
+        let target = evaluate(&ranks, &user(1, 5000, 100), now);
+        assert!(ranks.contains_key(&target));
+    }
+}
@@ -0,0 +1,40 @@
+use crate::data::kv::KVDB;
+use crate::data::ratio_watch as ratio_watch_model;
+use crate::error::Error;
+use chrono::{Duration, Utc};
+
+/// Lift recovered users first, then ban those out of time,
+/// and finally put new low ratio users on watch.
+pub async fn run(client: sqlx::PgPool) -> Result<(), Error> {
+    let ratio = KVDB
+        .clone()
+        .get_float("config", "BAN UPLOAD RATIO".as_ref())?
+        .unwrap();
+    let newbie_days = KVDB
+        .clone()
+        .get_number("config", "NEWBIE TERM".as_ref())?
+        .unwrap();
+    let watch_days = KVDB
+        .clone()
+        .get_number("config", "RATIO WATCH DAYS".as_ref())?
+        .unwrap();
+    let now = Utc::now();
+
+    let lifted = ratio_watch_model::lift_recovered_watches(&client, ratio).await?;
+    let banned = ratio_watch_model::ban_expired_watches(&client, ratio).await?;
+    let watched = ratio_watch_model::watch_low_ratio_users(
+        &client,
+        now - Duration::days(newbie_days),
+        ratio,
+        now + Duration::days(watch_days),
+    )
+    .await?;
+    log::info!(
+        "ratio watch: {} watched, {} banned, {} lifted",
+        watched,
+        banned,
+        lifted
+    );
+
+    Ok(())
+}
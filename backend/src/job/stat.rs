@@ -0,0 +1,11 @@
+use crate::data::stat as stat_model;
+use crate::error::Error;
+use chrono::{Duration, Utc};
+
+/// Refresh the snapshot of today, so the last run of a day is what is kept.
+pub async fn run(client: sqlx::PgPool) -> Result<(), Error> {
+    let users = stat_model::snapshot_user_stats(&client, Utc::now() - Duration::hours(1)).await?;
+    log::info!("stat: {} users snapshot", users);
+
+    Ok(())
+}
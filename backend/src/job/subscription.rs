@@ -0,0 +1,36 @@
+use crate::data::kv::KVDB;
+use crate::data::subscription as subscription_model;
+use crate::error::Error;
+use chrono::{Duration, Utc};
+
+/// Notify torrents left over by the hourly limit to users in instant mode,
+/// and send digests to users in digest mode once their oldest torrent waits
+/// for `SUBSCRIPTION DIGEST HOURS`.
+pub async fn run(client: sqlx::PgPool) -> Result<(), Error> {
+    let hourly_limit = KVDB
+        .clone()
+        .get_number("config", "SUBSCRIPTION HOURLY LIMIT".as_ref())?
+        .unwrap();
+    let digest_hours = KVDB
+        .clone()
+        .get_number("config", "SUBSCRIPTION DIGEST HOURS".as_ref())?
+        .unwrap();
+    let now = Utc::now();
+
+    let mut notified = 0;
+    for uid in subscription_model::find_pending_uids(&client, false, now).await? {
+        notified += subscription_model::notify_matches(&client, uid, Some(hourly_limit)).await?;
+    }
+    let mut digested = 0;
+    let matched_before = now - Duration::hours(digest_hours);
+    for uid in subscription_model::find_pending_uids(&client, true, matched_before).await? {
+        digested += subscription_model::notify_matches(&client, uid, None).await?;
+    }
+    log::info!(
+        "subscription: {} torrents notified, {} in digests",
+        notified,
+        digested
+    );
+
+    Ok(())
+}
@@ -2,6 +2,7 @@ mod config;
 mod controller;
 pub mod data;
 mod error;
+mod job;
 mod search;
 mod util;
 
@@ -46,23 +47,16 @@ fn init_settings() {
             .put("config", key.as_ref(), val.as_ref())
             .unwrap();
     }
-    // TODO: Reflection?
-    KVDB.clone()
-        .put("config", "INVITE CONSUME".as_ref(), &5000_f64.to_ne_bytes())
-        .unwrap();
-    KVDB.clone()
-        .put(
-            "config",
-            "BAN UPLOAD RATIO".as_ref(),
-            &0.3_f64.to_ne_bytes(),
-        )
-        .unwrap();
-    KVDB.clone()
-        .put("config", "NEWBIE TERM".as_ref(), &14_i64.to_ne_bytes())
-        .unwrap();
-    KVDB.clone()
-        .put("config", "LOGIN EXPIRE DAY".as_ref(), &3_i64.to_ne_bytes())
-        .unwrap();
+    for (key, val) in controller::FLOAT_SITE_SETTING.iter() {
+        KVDB.clone()
+            .put("config", key.as_ref(), &val.to_ne_bytes())
+            .unwrap();
+    }
+    for (key, val) in controller::NUMBER_SITE_SETTING.iter() {
+        KVDB.clone()
+            .put("config", key.as_ref(), &val.to_ne_bytes())
+            .unwrap();
+    }
 }
 
 #[actix_web::main]
@@ -77,6 +71,7 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("unable to connect to database");
     initializing_search(&pool).await;
+    job::start_jobs(&pool);
     println!("⭐⭐⭐⭐⭐⭐⭐⭐⭐SOPT is running⭐⭐⭐⭐⭐⭐⭐⭐⭐");
 
     HttpServer::new(move || {
@@ -103,6 +103,38 @@ impl SearchEngine {
     }
 }
 
+/// Match new torrents against subscriptions, given as keywords and tags.
+/// Torrents are given with their search tokens and tags, and match a subscription
+/// when they have all of its tags and are found by its keywords if there are any.
+/// It returns ids of matched torrents for each subscription.
+pub fn match_subscriptions(
+    torrents: &[(i64, Vec<String>, Vec<String>)],
+    subscriptions: &[(&[String], &[String])],
+) -> Vec<Vec<i64>> {
+    let mut engine = SearchEngine::new(0.8);
+    for (id, tokens, _) in torrents {
+        engine.insert(*id, tokens.clone());
+    }
+
+    subscriptions
+        .iter()
+        .map(|(keywords, tags)| {
+            let found = if keywords.is_empty() {
+                torrents.iter().map(|(id, _, _)| *id).collect()
+            } else {
+                engine.search(keywords.to_vec())
+            };
+            torrents
+                .iter()
+                .filter(|(id, _, torrent_tags)| {
+                    found.contains(id) && tags.iter().all(|tag| torrent_tags.contains(tag))
+                })
+                .map(|(id, _, _)| *id)
+                .collect()
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,4 +204,39 @@ mod tests {
         let ids = engine.search(vec!["喵萌奶茶屋".to_string()]);
         assert_eq!(ids.is_empty(), true);
     }
+
+    #[test]
+    fn match_subscriptions_works() {
+        let torrents = vec![
+            (
+                1,
+                vec!["[桜都字幕组][回转企鹅罐 Mawaru Penguindrum][BDRIP][720P]".to_string()],
+                vec!["anime".to_string(), "bd".to_string()],
+            ),
+            (
+                2,
+                vec!["[VCB-Studio] Mawaru Penguindrum [1080p]".to_string()],
+                vec!["anime".to_string()],
+            ),
+            (
+                3,
+                vec!["Oppenheimer 2023 1080p".to_string()],
+                vec!["movie".to_string()],
+            ),
+        ];
+        let keywords = vec!["Penguindrum".to_string()];
+        let tags = vec!["anime".to_string(), "bd".to_string()];
+        let movie = vec!["movie".to_string()];
+
+        let ret = match_subscriptions(
+            &torrents,
+            &[
+                (&keywords, &[]),
+                (&keywords, &tags),
+                (&[], &movie),
+                (&[], &[]),
+            ],
+        );
+        assert_eq!(ret, vec![vec![1, 2], vec![1], vec![3], vec![1, 2, 3]]);
+    }
 }
@@ -1,6 +1,6 @@
 use crate::config::CONFIG;
 use crate::error::{error_string, Error};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use pest::Parser;
 use pest_derive::*;
 use rand::{thread_rng, Rng};
@@ -165,36 +165,295 @@ pub fn decode_and_verify_jwt(token: &str, secret: &[u8]) -> Result<Claim, Error>
     }
 }
 
-use crate::data::torrent::{Torrent, TorrentTable};
+use crate::data::torrent::{Info, Torrent, TorrentTable};
+use crate::data::FileNode;
+
+/// lists and dicts nested deeper are refused, no torrent needs that many
+const BENCODE_MAX_DEPTH: usize = 64;
+
+/// Find where the bencoded value starting at `pos` ends.
+/// It walks nested values without recursion, so a crafted file
+/// cannot overflow the stack.
+fn bencode_value_end(buf: &[u8], mut pos: usize) -> Result<usize, Error> {
+    let broken = || Error::RequestError("broken torrent file".to_string());
+    let find = |from: usize, c: u8| {
+        buf.get(from..)
+            .and_then(|rest| rest.iter().position(|&x| x == c))
+            .map(|i| from + i)
+            .ok_or_else(broken)
+    };
+    let mut depth = 0;
+    loop {
+        match buf.get(pos).ok_or_else(broken)? {
+            b'i' => pos = find(pos, b'e')? + 1,
+            b'l' | b'd' => {
+                depth += 1;
+                if depth > BENCODE_MAX_DEPTH {
+                    return Err(Error::RequestError(
+                        "torrent file nested too deep".to_string(),
+                    ));
+                }
+                pos += 1;
+            }
+            b'e' if depth > 0 => {
+                depth -= 1;
+                pos += 1;
+            }
+            b'0'..=b'9' => {
+                let colon = find(pos, b':')?;
+                let len: usize = std::str::from_utf8(&buf[pos..colon])
+                    .ok()
+                    .and_then(|len| len.parse().ok())
+                    .ok_or_else(broken)?;
+                pos = (colon + 1)
+                    .checked_add(len)
+                    .filter(|&end| end <= buf.len())
+                    .ok_or_else(broken)?;
+            }
+            _ => return Err(broken()),
+        }
+        if depth == 0 {
+            return Ok(pos);
+        }
+    }
+}
+
+/// keys and raw values of a bencoded dict
+type BencodeEntries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Split a bencoded dict into keys and raw values, in their original order
+fn bencode_dict_entries(buf: &[u8]) -> Result<BencodeEntries, Error> {
+    if buf.first() != Some(&b'd') {
+        return Err(Error::RequestError("broken torrent file".to_string()));
+    }
+    let mut ret = vec![];
+    let mut pos = 1;
+    while buf.get(pos).is_some_and(|&c| c != b'e') {
+        if !buf[pos].is_ascii_digit() {
+            return Err(Error::RequestError("broken torrent file".to_string()));
+        }
+        let key_end = bencode_value_end(buf, pos)?;
+        // the key is a string, so there must be a colon
+        let key_start = pos + buf[pos..].iter().position(|&c| c == b':').unwrap_or(0) + 1;
+        let value_end = bencode_value_end(buf, key_end)?;
+        ret.push((
+            buf[key_start..key_end].to_vec(),
+            buf[key_end..value_end].to_vec(),
+        ));
+        pos = value_end;
+    }
+    Ok(ret)
+}
+
+/// Set `private` and `source` of a bencoded info dict while keeping it
+/// byte-exact otherwise, so that unknown keys like `md5sum` or `name.utf-8`
+/// are kept and infohash only changes when it has to.
+fn patch_info(info: &[u8], source: Option<&str>) -> Result<Vec<u8>, Error> {
+    let mut entries = bencode_dict_entries(info)?;
+    let mut patches = vec![(b"private".to_vec(), b"i1e".to_vec())];
+    if let Some(source) = source {
+        patches.push((
+            b"source".to_vec(),
+            format!("{}:{}", source.len(), source).into_bytes(),
+        ));
+    }
+
+    let mut changed = false;
+    for (key, value) in patches {
+        match entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) if *v == value => {}
+            Some((_, v)) => {
+                *v = value;
+                changed = true;
+            }
+            None => {
+                // keys of bencoded dict are sorted as raw strings
+                let at = entries
+                    .iter()
+                    .position(|(k, _)| *k > key)
+                    .unwrap_or(entries.len());
+                entries.insert(at, (key, value));
+                changed = true;
+            }
+        }
+    }
+    if !changed {
+        return Ok(info.to_vec());
+    }
+
+    let mut ret = vec![b'd'];
+    for (mut key, mut value) in entries {
+        ret.append(&mut format!("{}:", key.len()).into_bytes());
+        ret.append(&mut key);
+        ret.append(&mut value);
+    }
+    ret.push(b'e');
+    Ok(ret)
+}
+
+/// What uploaded torrents are checked against
+/// 1. source: `source` set into info dict
+/// 2. forbidden_extensions: lowercase extensions, `torrent` is always forbidden
+/// 3. forbidden_names: lowercase file names
+/// 4. max_files: maximum number of files
+#[derive(Default)]
+pub struct UploadPolicy {
+    pub source: Option<String>,
+    pub forbidden_extensions: Vec<String>,
+    pub forbidden_names: Vec<String>,
+    pub max_files: Option<usize>,
+}
+
+/// Check parsed torrent against upload policy, every violation is listed
+pub fn validate_torrent(torrent: &Torrent, policy: &UploadPolicy) -> Vec<String> {
+    let info = &torrent.info;
+    let mut ret = vec![];
+    if !info.is_v1() && !info.is_v2() {
+        ret.push("neither pieces nor file tree found".to_string());
+        return ret;
+    }
+    if info.piece_length <= 0 {
+        ret.push("piece length must be positive".to_string());
+        return ret;
+    }
+    // lengths are given by uploader, overflow is a violation
+    let piece_count = |length: i64| {
+        length
+            .checked_add(info.piece_length - 1)
+            .map(|length| length / info.piece_length)
+    };
+
+    if let Some(pieces) = &info.pieces {
+        match info.v1_length().and_then(piece_count) {
+            None => ret.push("length of pieces overflows".to_string()),
+            Some(_) if pieces.len() % 20 != 0 => ret.push("pieces are not in 20 bytes".to_string()),
+            Some(expected) if pieces.len() as i64 / 20 != expected => ret.push(format!(
+                "{} pieces found while {} expected",
+                pieces.len() / 20,
+                expected
+            )),
+            Some(_) => (),
+        }
+    }
+    if info.is_v2() {
+        if info.piece_length < 16384 || info.piece_length & (info.piece_length - 1) != 0 {
+            ret.push("piece length must be a power of two and at least 16 KiB".to_string());
+        }
+        for (path, attr) in info.tree_files() {
+            if attr.length <= info.piece_length {
+                continue;
+            }
+            let layer = attr.pieces_root.as_ref().and_then(|root| {
+                torrent
+                    .piece_layers
+                    .as_ref()
+                    .and_then(|layers| layers.get(root))
+            });
+            let expected = piece_count(attr.length).and_then(|count| count.checked_mul(32));
+            if expected.is_none() || layer.map(|layer| layer.len() as i64) != expected {
+                ret.push(format!("piece layer mismatched for {}", path.join("/")));
+            }
+        }
+    }
+
+    let files = info.file_list();
+    if files.is_empty() {
+        ret.push("no file found".to_string());
+    }
+    if info.total_length().is_none() {
+        ret.push("total length overflows".to_string());
+    }
+    if let Some(max) = policy.max_files.filter(|&max| files.len() > max) {
+        ret.push(format!("{} files exceed the limit of {}", files.len(), max));
+    }
+    for (path, length) in &files {
+        let joined = path.join("/");
+        if *length < 0 {
+            ret.push(format!("negative length of {}", joined));
+        }
+        if path.is_empty()
+            || path
+                .iter()
+                .any(|p| p.is_empty() || p == "." || p == ".." || p.contains(['/', '\\']))
+        {
+            ret.push(format!("unsafe path {}", joined));
+            continue;
+        }
+        let name = path.last().unwrap().to_lowercase();
+        let extension = name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext)
+            .unwrap_or_default();
+        if extension == "torrent" {
+            ret.push(format!("nested torrent file {}", joined));
+        } else if policy.forbidden_extensions.iter().any(|e| e == extension) {
+            ret.push(format!("forbidden file extension {}", joined));
+        }
+        if policy.forbidden_names.contains(&name) {
+            ret.push(format!("forbidden file name {}", joined));
+        }
+    }
+    ret
+}
+
 /// Parse uploaded torrent file and convert into a table row
-pub fn parse_torrent_file(buf: &[u8]) -> Result<TorrentTable, Error> {
-    use serde_bencode::{from_bytes, to_bytes};
+///
+/// Info dict is kept as uploaded except `private` and `source`,
+/// see `patch_info`.
+///
+/// v1 and hybrid torrents are announced by sha1 infohash, and
+/// v2 only torrents by sha256 infohash truncated to 20 bytes (BEP 52).
+pub fn parse_torrent_file(buf: &[u8], policy: &UploadPolicy) -> Result<TorrentTable, Error> {
+    use serde_bencode::from_bytes;
     use sha1::{Digest, Sha1};
+    use sha2::Sha256;
 
-    let mut ret = from_bytes::<Torrent>(buf).map_err(error_string)?;
-    ret.info.private = Some(1);
-    let info = to_bytes(&ret.info).map_err(error_string)?;
-    let mut hasher = Sha1::new();
-    hasher.update(&info);
-    let res: Vec<u8> = hasher
-        .finalize()
-        .as_slice()
-        .try_into()
-        .map_err(error_string)?;
-    let infohash = hex::encode(res);
+    // serde_bencode recurses on nested values, check the depth first
+    bencode_value_end(buf, 0)?;
+    let ret = from_bytes::<Torrent>(buf).map_err(error_string)?;
+    let violations = validate_torrent(&ret, policy);
+    if !violations.is_empty() {
+        return Err(Error::InvalidTorrent(violations));
+    }
+    let entries = bencode_dict_entries(buf)?;
+    let raw_value = |name: &[u8]| {
+        entries
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let raw_info =
+        raw_value(b"info").ok_or_else(|| Error::RequestError("broken torrent file".to_string()))?;
+    let info = patch_info(&raw_info, policy.source.as_deref())?;
+    let infohashv2 = if ret.info.is_v2() {
+        Some(hex::encode(Sha256::digest(&info)))
+    } else {
+        None
+    };
+    let infohash = match &infohashv2 {
+        Some(v2) if !ret.info.is_v1() => v2[..40].to_string(),
+        _ => hex::encode(Sha1::digest(&info)),
+    };
+    let piecelayers = if ret.info.is_v2() {
+        raw_value(b"piece layers")
+    } else {
+        None
+    };
 
-    let length = ret.info.length.unwrap_or(ret.info.piece_length * 8);
-    let files = ret
+    let length = ret
         .info
-        .files
-        .unwrap_or_default()
-        .iter()
-        .map(|file| {
-            file.path
-                .iter()
-                .fold("".to_string(), |acc, x| format!("{}{}{}", acc, "/", x)) // smartstring breaks String add
-        })
-        .collect();
+        .total_length()
+        .ok_or_else(|| Error::InvalidTorrent(vec!["total length overflows".to_string()]))?;
+    let file_list = ret.info.file_list();
+    // a single file torrent has no file list
+    let (files, filesizes) = if ret.info.is_single_file() {
+        (vec![], vec![])
+    } else {
+        file_list
+            .into_iter()
+            .map(|(path, length)| (format!("/{}", path.join("/")), length))
+            .unzip()
+    };
 
     Ok(TorrentTable {
         id: 1919810,
@@ -204,34 +463,490 @@ pub fn parse_torrent_file(buf: &[u8]) -> Result<TorrentTable, Error> {
         files,
         info,
         infohash,
+        infohashv2,
+        piecelayers,
+        filesizes,
     })
 }
 
-/// Generate torrent file buf with custom announce and passkey
+/// Similarity of two torrents from 0 to 1, that is the share of bytes
+/// in files having the same size and a similar name.
+pub fn torrent_similarity(a: &Info, b: &Info) -> f64 {
+    use strsim::normalized_levenshtein;
+
+    let name = |path: &[String]| path.last().cloned().unwrap_or_default().to_lowercase();
+    let files_a = a.file_list();
+    let files_b = b.file_list();
+    let total = a.total_length().max(b.total_length()).unwrap_or(0);
+    if total <= 0 {
+        return 0.0;
+    }
+
+    let mut used = vec![false; files_b.len()];
+    let mut matched = 0;
+    for (path, length) in &files_a {
+        let name_a = name(path);
+        let best = files_b
+            .iter()
+            .enumerate()
+            .filter(|(i, (_, len))| !used[*i] && len == length)
+            .map(|(i, (path, _))| (i, normalized_levenshtein(&name_a, &name(path))))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, _)) = best.filter(|(_, score)| *score >= 0.5) {
+            used[i] = true;
+            matched += length;
+        }
+    }
+    matched as f64 / total as f64
+}
+
+/// Build the file tree of a torrent from its flattened `files` and their sizes,
+/// rooted at the torrent name.
+///
+/// Files are sorted by path and only those in `range` are listed, so that
+/// torrents with thousands of files can be shown page by page, while
+/// `size` and `count` of directories always cover all files under them.
+pub fn build_file_tree(
+    name: &str,
+    files: &[String],
+    sizes: &[i64],
+    range: std::ops::Range<usize>,
+) -> FileNode {
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct Node {
+        size: i64,
+        count: i64,
+        listed: bool,
+        children: Option<BTreeMap<String, Node>>,
+    }
+
+    impl Node {
+        fn into_file_node(self, name: String) -> FileNode {
+            FileNode {
+                name,
+                size: self.size,
+                count: self.count,
+                children: self.children.map(|children| {
+                    children
+                        .into_iter()
+                        .filter(|(_, child)| child.listed)
+                        .map(|(name, child)| child.into_file_node(name))
+                        .collect()
+                }),
+            }
+        }
+    }
+
+    let mut paths: Vec<(Vec<&str>, i64)> = files
+        .iter()
+        .zip(sizes)
+        .map(|(path, &size)| (path.split('/').filter(|s| !s.is_empty()).collect(), size))
+        .collect();
+    paths.sort();
+
+    let mut root = Node {
+        listed: true,
+        children: Some(BTreeMap::new()),
+        ..Default::default()
+    };
+    for (i, (path, size)) in paths.into_iter().enumerate() {
+        let listed = range.contains(&i);
+        let mut node = &mut root;
+        node.size += size;
+        node.count += 1;
+        for (depth, part) in path.iter().enumerate() {
+            let is_dir = depth + 1 < path.len();
+            node = node
+                .children
+                .get_or_insert_with(BTreeMap::new)
+                .entry(part.to_string())
+                .or_insert_with(|| Node {
+                    children: is_dir.then(BTreeMap::new),
+                    ..Default::default()
+                });
+            node.size += size;
+            node.count += 1;
+            node.listed |= listed;
+        }
+    }
+    root.into_file_node(name.to_string())
+}
+
+/// Writer of bencoded dict, keys are always written in sorted order
+#[derive(Default)]
+struct BencodeDict(std::collections::BTreeMap<Vec<u8>, Vec<u8>>);
+
+impl BencodeDict {
+    /// `value` is already bencoded
+    fn raw(mut self, key: &str, value: Vec<u8>) -> Self {
+        self.0.insert(key.as_bytes().to_vec(), value);
+        self
+    }
+
+    fn string(self, key: &str, value: &str) -> Self {
+        self.raw(key, bencode_string(value))
+    }
+
+    fn int(self, key: &str, value: i64) -> Self {
+        self.raw(key, format!("i{}e", value).into_bytes())
+    }
+
+    fn list(self, key: &str, items: Vec<Vec<u8>>) -> Self {
+        self.raw(key, bencode_list(items))
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut ret = vec![b'd'];
+        for (key, mut value) in self.0 {
+            ret.append(&mut format!("{}:", key.len()).into_bytes());
+            ret.extend(key);
+            ret.append(&mut value);
+        }
+        ret.push(b'e');
+        ret
+    }
+}
+
+fn bencode_string(value: &str) -> Vec<u8> {
+    format!("{}:{}", value.len(), value).into_bytes()
+}
+
+/// `items` are already bencoded
+fn bencode_list(items: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut ret = vec![b'l'];
+    items.into_iter().for_each(|mut item| ret.append(&mut item));
+    ret.push(b'e');
+    ret
+}
+
+/// What is written into generated torrents besides info dict
+/// 1. announce_list: tiers of announce addresses (BEP 12), the first one is also `announce`
+/// 2. web_seeds: `url-list` (BEP 19)
+/// 3. comment: comment by uploader
+/// 4. created_by: name and version of the site
+/// 5. creation_date: unix timestamp
+pub struct TorrentMeta {
+    pub announce_list: Vec<Vec<String>>,
+    pub web_seeds: Vec<String>,
+    pub comment: Option<String>,
+    pub created_by: String,
+    pub creation_date: i64,
+}
+
+/// Announce address of a user for a torrent
 ///
 /// Announce format: {announce_addr}?passkey={passkey}&tid={torrent id}&uid={user id}
+pub fn personal_announce(addr: &str, passkey: &str, tid: i64, uid: i64) -> String {
+    let sep = if addr.contains('?') { '&' } else { '?' };
+    format!("{}{}passkey={}&tid={}&uid={}", addr, sep, passkey, tid, uid)
+}
+
+/// Generate torrent file buf with custom announce and passkey
+///
+/// Announce addresses are personal, see `personal_announce`.
+///
+/// Piece layers of v2 and hybrid torrents are attached as they are.
 pub fn generate_torrent_file(
-    mut info: Vec<u8>,
+    info: Vec<u8>,
+    piece_layers: Option<Vec<u8>>,
+    meta: &TorrentMeta,
     passkey: &str,
     tid: i64,
     uid: i64,
-    comment: &str,
 ) -> Vec<u8> {
-    use serde_bencode::to_string;
+    let personal = |addr: &String| bencode_string(&personal_announce(addr, passkey, tid, uid));
+
+    let mut torrent = BencodeDict::default()
+        .raw("info", info)
+        .string("created by", &meta.created_by)
+        .int("creation date", meta.creation_date);
+    if let Some(addr) = meta.announce_list.iter().flatten().next() {
+        torrent = torrent.raw("announce", personal(addr));
+    }
+    if meta.announce_list.iter().flatten().nth(1).is_some() {
+        let tiers = meta
+            .announce_list
+            .iter()
+            .filter(|tier| !tier.is_empty())
+            .map(|tier| bencode_list(tier.iter().map(personal).collect()))
+            .collect();
+        torrent = torrent.list("announce-list", tiers);
+    }
+    if !meta.web_seeds.is_empty() {
+        let seeds = meta.web_seeds.iter().map(|s| bencode_string(s)).collect();
+        torrent = torrent.list("url-list", seeds);
+    }
+    if let Some(comment) = meta.comment.as_deref().filter(|c| !c.is_empty()) {
+        torrent = torrent.string("comment", comment);
+    }
+    if let Some(piece_layers) = piece_layers {
+        torrent = torrent.raw("piece layers", piece_layers);
+    }
+
+    torrent.finish()
+}
+
+/// Percent-encode everything except unreserved characters (RFC 3986)
+pub fn percent_encode(input: &str) -> String {
+    input
+        .bytes()
+        .map(|c| match c {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (c as char).to_string()
+            }
+            _ => format!("%{:02X}", c),
+        })
+        .collect()
+}
+
+/// Generate magnet link with personal trackers
+///
+/// v1 infohash is given by `urn:btih` and v2 one by `urn:btmh` (BEP 9, BEP 52),
+/// both are given for hybrid torrents.
+pub fn generate_magnet(
+    torrent: &TorrentTable,
+    meta: &TorrentMeta,
+    passkey: &str,
+    uid: i64,
+) -> String {
+    let mut params = vec![];
+    // infohash of v2 only torrent is the truncated v2 one
+    if !torrent
+        .infohashv2
+        .as_ref()
+        .is_some_and(|v2| v2.starts_with(&torrent.infohash))
+    {
+        params.push(format!("xt=urn:btih:{}", torrent.infohash));
+    }
+    if let Some(v2) = &torrent.infohashv2 {
+        // multihash of sha256 with 32 bytes digest
+        params.push(format!("xt=urn:btmh:1220{}", v2));
+    }
+    params.push(format!("dn={}", percent_encode(&torrent.name)));
+    params.push(format!("xl={}", torrent.length));
+    for addr in meta.announce_list.iter().flatten() {
+        let tr = personal_announce(addr, passkey, torrent.id, uid);
+        params.push(format!("tr={}", percent_encode(&tr)));
+    }
+    for seed in &meta.web_seeds {
+        params.push(format!("ws={}", percent_encode(seed)));
+    }
+    format!("magnet:?{}", params.join("&"))
+}
+
+/// A ZIP archive written file by file, so that it can be streamed without
+/// holding every file. Files are stored without compression as they are
+/// mostly piece hashes, and dated 1980-01-01 as the format allows no earlier.
+#[derive(Default)]
+pub struct ZipStream {
+    written: u64,
+    // name, crc32, size and offset of local header of every file
+    entries: Vec<(String, u32, u32, u32)>,
+}
+
+impl ZipStream {
+    const VERSION: u16 = 20;
+    // names are UTF-8
+    const FLAGS: u16 = 1 << 11;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// local header and content of a file
+    pub fn add(&mut self, name: &str, content: &[u8]) -> Result<Vec<u8>, Error> {
+        let too_large = |_| Error::RequestError("archive too large".to_string());
+        let offset = u32::try_from(self.written).map_err(too_large)?;
+        let size = u32::try_from(content.len()).map_err(too_large)?;
+        let name_len = u16::try_from(name.len()).map_err(too_large)?;
+        let crc = crc32fast::hash(content);
+
+        let mut ret = Vec::with_capacity(30 + name.len() + content.len());
+        ret.extend_from_slice(&0x04034b50_u32.to_le_bytes());
+        for field in [Self::VERSION, Self::FLAGS, 0, 0, Self::DOS_DATE] {
+            ret.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc, size, size] {
+            ret.extend_from_slice(&field.to_le_bytes());
+        }
+        ret.extend_from_slice(&name_len.to_le_bytes());
+        ret.extend_from_slice(&0_u16.to_le_bytes());
+        ret.extend_from_slice(name.as_bytes());
+        ret.extend_from_slice(content);
+
+        self.written += ret.len() as u64;
+        self.entries.push((name.to_string(), crc, size, offset));
+        Ok(ret)
+    }
 
-    let announce_address = to_string(&format!(
-        "{}?passkey={}&tid={}&uid={}",
-        CONFIG.announce_addr, passkey, tid, uid
-    ))
-    .unwrap();
-    let comment = to_string(&comment).unwrap();
+    /// central directory, which ends the archive
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        let too_large = |_| Error::RequestError("archive too large".to_string());
+        let start = u32::try_from(self.written).map_err(too_large)?;
+        let count = u16::try_from(self.entries.len()).map_err(too_large)?;
 
-    let mut hand_ser = "d4:info".to_string().into_bytes();
-    hand_ser.append(&mut info);
-    hand_ser
-        .append(&mut format!("8:announce{}7:comment{}e", announce_address, comment).into_bytes());
+        let mut ret = vec![];
+        for (name, crc, size, offset) in &self.entries {
+            ret.extend_from_slice(&0x02014b50_u32.to_le_bytes());
+            for field in [
+                Self::VERSION,
+                Self::VERSION,
+                Self::FLAGS,
+                0,
+                0,
+                Self::DOS_DATE,
+            ] {
+                ret.extend_from_slice(&field.to_le_bytes());
+            }
+            for field in [*crc, *size, *size] {
+                ret.extend_from_slice(&field.to_le_bytes());
+            }
+            // name length was checked when added
+            for field in [name.len() as u16, 0, 0, 0, 0] {
+                ret.extend_from_slice(&field.to_le_bytes());
+            }
+            ret.extend_from_slice(&0_u32.to_le_bytes());
+            ret.extend_from_slice(&offset.to_le_bytes());
+            ret.extend_from_slice(name.as_bytes());
+        }
+        let size = u32::try_from(ret.len()).map_err(too_large)?;
+        ret.extend_from_slice(&0x06054b50_u32.to_le_bytes());
+        for field in [0, 0, count, count] {
+            ret.extend_from_slice(&field.to_le_bytes());
+        }
+        ret.extend_from_slice(&size.to_le_bytes());
+        ret.extend_from_slice(&start.to_le_bytes());
+        ret.extend_from_slice(&0_u16.to_le_bytes());
+        Ok(ret)
+    }
+}
 
-    hand_ser
+/// An item of RSS and Atom feeds
+/// 1. link: page of the torrent, also used as id
+/// 2. enclosure: address to download the torrent file
+/// 3. length: length of torrent content
+pub struct FeedItem {
+    pub title: String,
+    pub link: String,
+    pub author: String,
+    pub description: Option<String>,
+    pub categories: Vec<String>,
+    pub date: DateTime<Utc>,
+    pub enclosure: String,
+    pub length: i64,
+}
+
+pub fn xml_escape(input: &str) -> String {
+    let mut ret = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Generate RSS 2.0 feed
+pub fn generate_rss(title: &str, link: &str, items: &[FeedItem]) -> String {
+    let mut ret = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <rss version=\"2.0\"><channel><title>{0}</title><link>{1}</link>\
+        <description>{0}</description>\n",
+        xml_escape(title),
+        xml_escape(link)
+    );
+    for item in items {
+        ret.push_str(&format!(
+            "<item><title>{0}</title><link>{1}</link><guid isPermaLink=\"true\">{1}</guid>\
+            <pubDate>{2}</pubDate>",
+            xml_escape(&item.title),
+            xml_escape(&item.link),
+            item.date.to_rfc2822()
+        ));
+        if let Some(description) = &item.description {
+            ret.push_str(&format!(
+                "<description>{}</description>",
+                xml_escape(description)
+            ));
+        }
+        for category in &item.categories {
+            ret.push_str(&format!("<category>{}</category>", xml_escape(category)));
+        }
+        ret.push_str(&format!(
+            "<enclosure url=\"{}\" length=\"{}\" type=\"application/x-bittorrent\"/></item>\n",
+            xml_escape(&item.enclosure),
+            item.length
+        ));
+    }
+    ret.push_str("</channel></rss>\n");
+    ret
+}
+
+/// Generate Atom feed, updated when the latest item is
+pub fn generate_atom(title: &str, link: &str, items: &[FeedItem]) -> String {
+    let updated = items
+        .iter()
+        .map(|item| item.date)
+        .max()
+        .unwrap_or_else(Utc::now);
+    let mut ret = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <feed xmlns=\"http://www.w3.org/2005/Atom\"><title>{0}</title><link href=\"{1}\"/>\
+        <id>{1}</id><updated>{2}</updated>\n",
+        xml_escape(title),
+        xml_escape(link),
+        updated.to_rfc3339()
+    );
+    for item in items {
+        ret.push_str(&format!(
+            "<entry><title>{0}</title><link href=\"{1}\"/><id>{1}</id><updated>{2}</updated>\
+            <author><name>{3}</name></author>",
+            xml_escape(&item.title),
+            xml_escape(&item.link),
+            item.date.to_rfc3339(),
+            xml_escape(&item.author)
+        ));
+        if let Some(description) = &item.description {
+            ret.push_str(&format!("<summary>{}</summary>", xml_escape(description)));
+        }
+        for category in &item.categories {
+            ret.push_str(&format!("<category term=\"{}\"/>", xml_escape(category)));
+        }
+        ret.push_str(&format!(
+            "<link rel=\"enclosure\" type=\"application/x-bittorrent\" length=\"{}\" href=\"{}\"/>\
+            </entry>\n",
+            item.length,
+            xml_escape(&item.enclosure)
+        ));
+    }
+    ret.push_str("</feed>\n");
+    ret
+}
+
+/// ranges in `data.prefixes[].prefix` of a RIPEstat announced-prefixes
+/// response, truncated and deduplicated, invalid ones are skipped
+pub fn announced_prefixes(resp: &serde_json::Value) -> Vec<String> {
+    use ipnet::IpNet;
+
+    let mut ret: Vec<String> = resp["data"]["prefixes"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|p| p["prefix"].as_str()?.parse::<IpNet>().ok())
+        .map(|net| net.trunc().to_string())
+        .collect();
+    ret.sort_unstable();
+    ret.dedup();
+    ret
 }
 
 #[cfg(test)]
@@ -310,4 +1025,508 @@ mod tests {
         assert!(ret1.is_err());
         assert!(ret2.is_err());
     }
+    #[test]
+    fn parse_hybrid_torrent_works() {
+        use sha1::{Digest, Sha1};
+        use sha2::Sha256;
+
+        let root = "r".repeat(32);
+        let info = format!(
+            "d9:file treed3:dird5:a.txtd0:d6:lengthi3e11:pieces root32:{}eeee\
+            5:filesld6:lengthi3e4:pathl3:dir5:a.txteee12:meta versioni2e\
+            4:name4:test12:piece lengthi16384e6:pieces20:{}7:privatei1ee",
+            root,
+            "p".repeat(20)
+        );
+        let buf = format!(
+            "d4:info{}12:piece layersd32:{}32:{}ee",
+            info,
+            root,
+            "l".repeat(32)
+        );
+        let ret = parse_torrent_file(buf.as_bytes(), &UploadPolicy::default()).unwrap();
+
+        assert_eq!(ret.info, info.as_bytes());
+        assert_eq!(ret.infohash, hex::encode(Sha1::digest(info.as_bytes())));
+        assert_eq!(
+            ret.infohashv2,
+            Some(hex::encode(Sha256::digest(info.as_bytes())))
+        );
+        assert_eq!(ret.files, vec!["/dir/a.txt".to_string()]);
+        assert_eq!(ret.filesizes, vec![3]);
+        assert_eq!(ret.length, 3);
+        assert!(ret.piecelayers.is_some());
+    }
+    #[test]
+    fn parse_v2_only_torrent_works() {
+        use sha2::{Digest, Sha256};
+
+        let info = format!(
+            "d9:file treed5:a.txtd0:d6:lengthi3e11:pieces root32:{}eee\
+            12:meta versioni2e4:name5:a.txt12:piece lengthi16384e7:privatei1ee",
+            "r".repeat(32)
+        );
+        let ret = parse_torrent_file(
+            format!("d4:info{}e", info).as_bytes(),
+            &UploadPolicy::default(),
+        )
+        .unwrap();
+        let v2 = hex::encode(Sha256::digest(info.as_bytes()));
+
+        assert_eq!(ret.infohash, v2[..40]);
+        assert_eq!(ret.infohashv2, Some(v2));
+        assert!(ret.files.is_empty());
+        assert_eq!(ret.length, 3);
+        assert!(parse_torrent_file(
+            b"d4:infod4:name1:a12:piece lengthi16384eee",
+            &UploadPolicy::default()
+        )
+        .is_err());
+    }
+    #[test]
+    fn keep_info_dict_works() {
+        use sha1::{Digest, Sha1};
+
+        // what mainstream clients hash is the info dict as it is,
+        // including unknown keys and even unsorted ones
+        let info = format!(
+            "d5:filesld4:attr1:x6:lengthi3e6:md5sum32:{}4:pathl5:a.txte\
+            10:path.utf-8l5:a.txteee4:name4:test10:name.utf-84:test\
+            12:piece lengthi16384e6:pieces20:{}7:privatei1e6:source4:SOPTe",
+            "0".repeat(32),
+            "p".repeat(20)
+        );
+        let buf = format!("d8:announce3:url4:info{}e", info);
+        let ret = parse_torrent_file(
+            buf.as_bytes(),
+            &UploadPolicy {
+                source: Some("SOPT".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(ret.info, info.as_bytes());
+        assert_eq!(ret.infohash, hex::encode(Sha1::digest(info.as_bytes())));
+        assert_eq!(ret.files, vec!["/a.txt".to_string()]);
+        assert_eq!(ret.filesizes, vec![3]);
+
+        let unsorted = b"d7:privatei1e4:name1:a6:pieces0:e";
+        assert_eq!(patch_info(unsorted, None).unwrap(), unsorted);
+
+        // the fixture has neither `private` nor `source` but unknown keys
+        // (`md5sum`, `name.utf-8`, `attr`); both infohashes are computed
+        // apart from this crate, by sha1sum over the bencoded info dict
+        let fixture = include_bytes!("../testdata/multi_file.torrent");
+        let entries = bencode_dict_entries(fixture).unwrap();
+        let (_, raw_info) = entries.iter().find(|(k, _)| k == b"info").unwrap();
+        assert_eq!(
+            hex::encode(Sha1::digest(raw_info)),
+            "4201d3ca695dba6bc34cec4177d238eebf4240cd"
+        );
+        let policy = UploadPolicy {
+            source: Some("SOPT".to_string()),
+            ..Default::default()
+        };
+        let ret = parse_torrent_file(fixture, &policy).unwrap();
+        assert_eq!(ret.infohash, "3222013c9c33407f3495a3113a9669156115711d");
+        for key in [&b"6:md5sum"[..], b"10:name.utf-8", b"4:attr"] {
+            assert!(ret.info.windows(key.len()).any(|w| w == key));
+        }
+        // nothing is injected into an info dict which has them already
+        assert_eq!(
+            patch_info(&ret.info, policy.source.as_deref()).unwrap(),
+            ret.info
+        );
+        assert_eq!(
+            ret.files,
+            vec![
+                "/docs/readme.txt".to_string(),
+                "/video/sample.bin".to_string()
+            ]
+        );
+        assert_eq!(ret.filesizes, vec![18000, 51200]);
+    }
+    #[test]
+    fn bencode_value_end_works() {
+        let buf = b"d1:ai1e1:bl1:xdee1:c3:abce";
+        assert_eq!(bencode_value_end(buf, 0).unwrap(), buf.len());
+        assert_eq!(bencode_value_end(buf, 4).unwrap(), 7);
+        assert_eq!(bencode_value_end(buf, 10).unwrap(), 17);
+        assert!(bencode_value_end(b"l1:a", 0).is_err());
+        assert!(bencode_value_end(b"e", 0).is_err());
+        assert!(bencode_value_end(b"5:abc", 0).is_err());
+
+        // deep nesting is refused instead of overflowing the stack
+        let nested = |depth: usize| [vec![b'l'; depth], vec![b'e'; depth]].concat();
+        assert_eq!(bencode_value_end(&nested(64), 0).unwrap(), 128);
+        assert!(bencode_value_end(&nested(65), 0).is_err());
+        let deep = [
+            b"d4:infod4:name1:a6:pieces0:1:x".to_vec(),
+            nested(100000),
+            b"ee".to_vec(),
+        ]
+        .concat();
+        assert!(parse_torrent_file(&deep, &UploadPolicy::default()).is_err());
+
+        // huge lengths neither overflow nor read out of bounds
+        assert!(bencode_value_end(b"18446744073709551615:a", 0).is_err());
+        assert!(bencode_value_end(b"99999999999999999999999:a", 0).is_err());
+        assert!(bencode_value_end(b"l18446744073709551610:ae", 0).is_err());
+    }
+    #[test]
+    fn patch_info_works() {
+        assert_eq!(
+            patch_info(b"d4:name1:a6:pieces0:e", None).unwrap(),
+            b"d4:name1:a6:pieces0:7:privatei1ee"
+        );
+        assert_eq!(
+            patch_info(b"d4:name1:a7:privatei0e6:source3:fooe", Some("SOPT")).unwrap(),
+            b"d4:name1:a7:privatei1e6:source4:SOPTe"
+        );
+        assert_eq!(
+            patch_info(b"d4:name1:a5:zzzzzi0ee", Some("S")).unwrap(),
+            b"d4:name1:a7:privatei1e6:source1:S5:zzzzzi0ee"
+        );
+        assert!(patch_info(b"d4:name1:", None).is_err());
+        assert!(patch_info(b"d4:name3:ae", None).is_err());
+        assert!(patch_info(b"di1e1:ae", None).is_err());
+    }
+    #[test]
+    fn generate_torrent_file_works() {
+        let info = b"d6:lengthi3e4:name1:a12:piece lengthi16384e\
+            6:pieces20:pppppppppppppppppppp7:privatei1ee";
+        let mut meta = TorrentMeta {
+            announce_list: vec![vec!["https://t.sopt.rs/announce".to_string()]],
+            web_seeds: vec![],
+            comment: None,
+            created_by: "SOPT".to_string(),
+            creation_date: 1700000000,
+        };
+        let single = generate_torrent_file(info.to_vec(), None, &meta, "passkey", 1, 2);
+        assert_eq!(single, include_bytes!("../testdata/single_tracker.torrent"));
+
+        meta.announce_list = vec![
+            vec![
+                "https://t.sopt.rs/announce".to_string(),
+                "udp://t.sopt.rs:6969/announce".to_string(),
+            ],
+            vec!["https://backup.sopt.rs/announce?via=backup".to_string()],
+        ];
+        meta.web_seeds = vec!["https://seed.sopt.rs/".to_string()];
+        meta.comment = Some("hello".to_string());
+        let layers = format!("d32:{}32:{}e", "r".repeat(32), "l".repeat(32));
+        let full = generate_torrent_file(
+            info.to_vec(),
+            Some(layers.into_bytes()),
+            &meta,
+            "passkey",
+            1,
+            2,
+        );
+        assert_eq!(full, include_bytes!("../testdata/announce_list.torrent"));
+
+        // info dict and so infohash survive the regeneration
+        for generated in [single, full] {
+            assert_eq!(
+                parse_torrent_file(&generated, &UploadPolicy::default())
+                    .unwrap()
+                    .info,
+                info
+            );
+        }
+    }
+    #[test]
+    fn validate_torrent_works() {
+        let buf = format!(
+            "d4:infod5:filesld6:lengthi3e4:pathl5:a.EXEee\
+            d6:lengthi3e4:pathl3:sub9:b.torrentee\
+            d6:lengthi3e4:pathl2:..1:cee\
+            d6:lengthi3e4:pathl9:Thumbs.dbee\
+            d4:attr1:p6:lengthi5e4:pathl4:.pad1:5eee\
+            4:name4:test12:piece lengthi16384e6:pieces40:{}ee",
+            "p".repeat(40)
+        );
+        let policy = UploadPolicy {
+            forbidden_extensions: vec!["exe".to_string()],
+            forbidden_names: vec!["thumbs.db".to_string()],
+            max_files: Some(3),
+            ..Default::default()
+        };
+        let torrent = serde_bencode::from_bytes::<Torrent>(buf.as_bytes()).unwrap();
+
+        assert_eq!(
+            validate_torrent(&torrent, &policy),
+            vec![
+                "2 pieces found while 1 expected",
+                "4 files exceed the limit of 3",
+                "forbidden file extension a.EXE",
+                "nested torrent file sub/b.torrent",
+                "unsafe path ../c",
+                "forbidden file name Thumbs.db",
+            ]
+        );
+        assert!(matches!(
+            parse_torrent_file(buf.as_bytes(), &policy),
+            Err(Error::InvalidTorrent(violations)) if violations.len() == 6
+        ));
+
+        let single = format!(
+            "d4:infod6:lengthi16385e4:name5:a.exe12:piece lengthi16384e6:pieces40:{}ee",
+            "p".repeat(40)
+        );
+        let torrent = serde_bencode::from_bytes::<Torrent>(single.as_bytes()).unwrap();
+        assert!(validate_torrent(&torrent, &UploadPolicy::default()).is_empty());
+        let ret = parse_torrent_file(single.as_bytes(), &UploadPolicy::default()).unwrap();
+        assert_eq!(ret.length, 16385);
+        assert!(ret.files.is_empty());
+
+        let overflow = format!(
+            "d4:infod5:filesld6:lengthi{0}e4:pathl1:aeed6:lengthi{0}e4:pathl1:beee\
+            4:name4:test12:piece lengthi16384e6:pieces20:{1}ee",
+            i64::MAX,
+            "p".repeat(20)
+        );
+        let torrent = serde_bencode::from_bytes::<Torrent>(overflow.as_bytes()).unwrap();
+        assert_eq!(
+            validate_torrent(&torrent, &UploadPolicy::default()),
+            vec!["length of pieces overflows", "total length overflows"]
+        );
+        let single = format!(
+            "d4:infod6:lengthi{}e4:name1:a12:piece lengthi16384e6:pieces20:{}ee",
+            i64::MAX,
+            "p".repeat(20)
+        );
+        let torrent = serde_bencode::from_bytes::<Torrent>(single.as_bytes()).unwrap();
+        assert_eq!(
+            validate_torrent(&torrent, &UploadPolicy::default()),
+            vec!["length of pieces overflows"]
+        );
+    }
+    #[test]
+    fn torrent_similarity_works() {
+        let info = |files: &str| {
+            serde_bencode::from_bytes::<Info>(
+                format!("d5:filesl{}e4:name1:a12:piece lengthi16384ee", files).as_bytes(),
+            )
+            .unwrap()
+        };
+        let origin = info(
+            "d6:lengthi1000e4:pathl12:S01E01.1080pee\
+            d6:lengthi1000e4:pathl12:S01E02.1080pee",
+        );
+        let reupload = info(
+            "d6:lengthi1000e4:pathl16:s01e01.1080p.mkvee\
+            d6:lengthi1000e4:pathl16:s01e02.1080p.mkvee\
+            d6:lengthi10e4:pathl8:info.nfoee",
+        );
+        let partial = info("d6:lengthi1000e4:pathl12:S01E01.1080pee");
+        let renamed = info(
+            "d6:lengthi1000e4:pathl5:aaaaaee\
+            d6:lengthi1000e4:pathl5:bbbbbee",
+        );
+
+        assert_eq!(torrent_similarity(&origin, &origin), 1.0);
+        assert!(torrent_similarity(&origin, &reupload) > 0.99);
+        assert_eq!(torrent_similarity(&origin, &partial), 0.5);
+        assert_eq!(torrent_similarity(&partial, &origin), 0.5);
+        assert_eq!(torrent_similarity(&origin, &renamed), 0.0);
+    }
+    #[test]
+    fn generate_magnet_works() {
+        let mut torrent = TorrentTable {
+            id: 1,
+            name: "Some Show S01".to_string(),
+            length: 3,
+            comment: None,
+            files: vec![],
+            info: vec![],
+            infohash: "a".repeat(40),
+            infohashv2: None,
+            piecelayers: None,
+            filesizes: vec![],
+        };
+        let meta = TorrentMeta {
+            announce_list: vec![
+                vec!["https://t.sopt.rs/announce".to_string()],
+                vec!["udp://t.sopt.rs:6969".to_string()],
+            ],
+            web_seeds: vec![],
+            comment: None,
+            created_by: "SOPT".to_string(),
+            creation_date: 1700000000,
+        };
+        let tr = "tr=https%3A%2F%2Ft.sopt.rs%2Fannounce%3Fpasskey%3Dkey%26tid%3D1%26uid%3D2\
+            &tr=udp%3A%2F%2Ft.sopt.rs%3A6969%3Fpasskey%3Dkey%26tid%3D1%26uid%3D2";
+
+        assert_eq!(
+            generate_magnet(&torrent, &meta, "key", 2),
+            format!(
+                "magnet:?xt=urn:btih:{}&dn=Some%20Show%20S01&xl=3&{}",
+                "a".repeat(40),
+                tr
+            )
+        );
+
+        // hybrid torrent
+        torrent.infohashv2 = Some("b".repeat(64));
+        assert!(
+            generate_magnet(&torrent, &meta, "key", 2).starts_with(&format!(
+                "magnet:?xt=urn:btih:{}&xt=urn:btmh:1220{}&",
+                "a".repeat(40),
+                "b".repeat(64)
+            ))
+        );
+
+        // v2 only torrent
+        torrent.infohash = "b".repeat(40);
+        assert!(generate_magnet(&torrent, &meta, "key", 2)
+            .starts_with(&format!("magnet:?xt=urn:btmh:1220{}&", "b".repeat(64))));
+        assert_eq!(percent_encode("a b/中~"), "a%20b%2F%E4%B8%AD~");
+    }
+    #[test]
+    fn build_file_tree_works() {
+        let file = |name: &str, size| FileNode {
+            name: name.to_string(),
+            size,
+            count: 1,
+            children: None,
+        };
+        let dir = |name: &str, size, count, children| FileNode {
+            name: name.to_string(),
+            size,
+            count,
+            children: Some(children),
+        };
+        let files = ["/b.nfo", "/CD1/a.mkv", "/CD2/a.mkv", "/CD1/sub/a.srt"].map(String::from);
+        let sizes = [1, 100, 200, 10];
+
+        assert_eq!(
+            build_file_tree("Movie", &files, &sizes, 0..10),
+            dir(
+                "Movie",
+                311,
+                4,
+                vec![
+                    dir(
+                        "CD1",
+                        110,
+                        2,
+                        vec![
+                            file("a.mkv", 100),
+                            dir("sub", 10, 1, vec![file("a.srt", 10)])
+                        ]
+                    ),
+                    dir("CD2", 200, 1, vec![file("a.mkv", 200)]),
+                    file("b.nfo", 1),
+                ]
+            )
+        );
+        // the second page lists CD2 only, with sizes of all files
+        assert_eq!(
+            build_file_tree("Movie", &files, &sizes, 2..3),
+            dir(
+                "Movie",
+                311,
+                4,
+                vec![dir("CD2", 200, 1, vec![file("a.mkv", 200)])]
+            )
+        );
+        assert_eq!(
+            build_file_tree("Movie", &files, &sizes, 4..8),
+            dir("Movie", 311, 4, vec![])
+        );
+    }
+    #[test]
+    fn zip_stream_works() {
+        use std::io::Read;
+
+        let files = vec![
+            ("[1] a.torrent".to_string(), b"d4:infod4:name1:aee".to_vec()),
+            (
+                "[2] 动画.torrent".to_string(),
+                b"d4:infod4:name1:bee".to_vec(),
+            ),
+            ("[3] empty.torrent".to_string(), vec![]),
+        ];
+        let mut zip = ZipStream::new();
+        let mut buf = vec![];
+        for (name, content) in &files {
+            buf.append(&mut zip.add(name, content).unwrap());
+        }
+        buf.append(&mut zip.finish().unwrap());
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(buf)).unwrap();
+        assert_eq!(archive.len(), 3);
+        for (i, (name, content)) in files.iter().enumerate() {
+            let mut file = archive.by_index(i).unwrap();
+            assert_eq!(file.name(), name);
+            let mut read = vec![];
+            file.read_to_end(&mut read).unwrap();
+            assert_eq!(&read, content);
+        }
+    }
+    #[test]
+    fn generate_feed_works() {
+        use chrono::TimeZone;
+
+        let items = vec![FeedItem {
+            title: "Tom & Jerry".to_string(),
+            link: "https://sopt.rs/torrent/1".to_string(),
+            author: "brethland".to_string(),
+            description: Some("<b>1080p</b>".to_string()),
+            categories: vec!["anime".to_string()],
+            date: Utc.with_ymd_and_hms(2023, 7, 1, 8, 0, 0).unwrap(),
+            enclosure: "https://sopt.rs/api/feed/download?id=1&passkey=key".to_string(),
+            length: 1024,
+        }];
+
+        assert_eq!(
+            generate_rss("SOPT", "https://sopt.rs", &items),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <rss version=\"2.0\"><channel><title>SOPT</title><link>https://sopt.rs</link>\
+            <description>SOPT</description>\n\
+            <item><title>Tom &amp; Jerry</title><link>https://sopt.rs/torrent/1</link>\
+            <guid isPermaLink=\"true\">https://sopt.rs/torrent/1</guid>\
+            <pubDate>Sat, 1 Jul 2023 08:00:00 +0000</pubDate>\
+            <description>&lt;b&gt;1080p&lt;/b&gt;</description><category>anime</category>\
+            <enclosure url=\"https://sopt.rs/api/feed/download?id=1&amp;passkey=key\" \
+            length=\"1024\" type=\"application/x-bittorrent\"/></item>\n\
+            </channel></rss>\n"
+        );
+        assert_eq!(
+            generate_atom("SOPT", "https://sopt.rs", &items),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <feed xmlns=\"http://www.w3.org/2005/Atom\"><title>SOPT</title>\
+            <link href=\"https://sopt.rs\"/><id>https://sopt.rs</id>\
+            <updated>2023-07-01T08:00:00+00:00</updated>\n\
+            <entry><title>Tom &amp; Jerry</title><link href=\"https://sopt.rs/torrent/1\"/>\
+            <id>https://sopt.rs/torrent/1</id><updated>2023-07-01T08:00:00+00:00</updated>\
+            <author><name>brethland</name></author>\
+            <summary>&lt;b&gt;1080p&lt;/b&gt;</summary><category term=\"anime\"/>\
+            <link rel=\"enclosure\" type=\"application/x-bittorrent\" length=\"1024\" \
+            href=\"https://sopt.rs/api/feed/download?id=1&amp;passkey=key\"/></entry>\n\
+            </feed>\n"
+        );
+    }
+    #[test]
+    fn announced_prefixes_works() {
+        let resp = serde_json::json!({
+            "data": {
+                "prefixes": [
+                    {"prefix": "203.0.113.0/24", "timelines": []},
+                    {"prefix": "2001:db8::/32"},
+                    {"prefix": "198.51.100.7/24"},
+                    {"prefix": "203.0.113.0/24"},
+                    {"prefix": "not a prefix"},
+                    {"timelines": []}
+                ],
+                "resource": "64496"
+            }
+        });
+        assert_eq!(
+            announced_prefixes(&resp),
+            vec!["198.51.100.0/24", "2001:db8::/32", "203.0.113.0/24"]
+        );
+        assert!(announced_prefixes(&serde_json::json!({"status": "error"})).is_empty());
+    }
 }
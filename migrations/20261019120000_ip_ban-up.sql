@@ -0,0 +1,11 @@
+-- Add migration script here
+DROP TABLE if exists ip_ban;
+CREATE TABLE ip_ban(
+    id BIGSERIAL PRIMARY KEY,
+    cidr VARCHAR NOT NULL UNIQUE,
+    reason VARCHAR NOT NULL DEFAULT '',
+    operator VARCHAR NOT NULL,
+    -- set if banned as a range announced by the autonomous system
+    asn BIGINT,
+    createTime TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,27 @@
+-- Add migration script here
+ALTER TABLE torrent_status
+    ADD COLUMN seedtime BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN lastannounce TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN finishtime TIMESTAMPTZ;
+
+DROP TABLE if exists hit_and_run;
+CREATE TABLE hit_and_run(
+    id BIGSERIAL PRIMARY KEY,
+    tid BIGINT NOT NULL REFERENCES torrent_info(id),
+    uid BIGINT NOT NULL REFERENCES users(id),
+    seedtime BIGINT NOT NULL,
+    upload BIGINT NOT NULL,
+    status INTEGER NOT NULL DEFAULT 0,
+    operator VARCHAR,
+    createTime TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE(tid, uid)
+);
+
+-- overrides of HNR site settings for a torrent, NULL falls back to the setting
+DROP TABLE if exists hit_and_run_rule;
+CREATE TABLE hit_and_run_rule(
+    tid BIGINT PRIMARY KEY REFERENCES torrent_info(id),
+    minSeedHours BIGINT,
+    minRatio DOUBLE PRECISION,
+    graceDays BIGINT
+);
@@ -0,0 +1,20 @@
+-- Add migration script here
+ALTER TABLE torrent_status
+    ADD COLUMN seeding BOOLEAN NOT NULL DEFAULT false;
+
+DROP TABLE if exists bonus_award;
+CREATE TABLE bonus_award(
+    id BIGSERIAL PRIMARY KEY,
+    uid BIGINT NOT NULL REFERENCES users(id),
+    amount DOUBLE PRECISION NOT NULL,
+    torrents INTEGER NOT NULL,
+    weight DOUBLE PRECISION NOT NULL,
+    createTime TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- last run of periodic jobs, so that they never run twice in one period
+DROP TABLE if exists job_run;
+CREATE TABLE job_run(
+    name VARCHAR PRIMARY KEY,
+    lastRun TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,37 @@
+-- Add migration script here
+ALTER TABLE user_info
+    ADD COLUMN invites INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN title VARCHAR(30);
+
+DROP TABLE if exists shop_item;
+CREATE TABLE shop_item(
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    description TEXT,
+    kind INTEGER NOT NULL,
+    value BIGINT NOT NULL DEFAULT 0,
+    price DOUBLE PRECISION NOT NULL,
+    stock BIGINT,
+    userLimit INTEGER,
+    available BOOLEAN NOT NULL DEFAULT TRUE,
+    CHECK ( price >= 0.0 )
+);
+
+DROP TABLE if exists purchase;
+CREATE TABLE purchase(
+    id BIGSERIAL PRIMARY KEY,
+    uid BIGINT NOT NULL REFERENCES users(id),
+    item BIGINT NOT NULL REFERENCES shop_item(id),
+    price DOUBLE PRECISION NOT NULL,
+    detail VARCHAR,
+    createTime TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+DROP TABLE if exists user_token;
+CREATE TABLE user_token(
+    uid BIGINT NOT NULL REFERENCES users(id),
+    kind INTEGER NOT NULL,
+    amount INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(uid, kind),
+    CHECK ( amount >= 0 )
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+DROP TABLE if exists money_ledger;
+CREATE TABLE money_ledger(
+    id BIGSERIAL PRIMARY KEY,
+    uid BIGINT NOT NULL REFERENCES users(id),
+    amount DOUBLE PRECISION NOT NULL,
+    balance DOUBLE PRECISION NOT NULL,
+    reason INTEGER NOT NULL,
+    counterparty VARCHAR(50),
+    note VARCHAR,
+    createTime TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX money_ledger_uid_idx ON money_ledger(uid, id);
@@ -0,0 +1,11 @@
+-- Add migration script here
+DROP TABLE if exists token_usage;
+CREATE TABLE token_usage(
+    id BIGSERIAL PRIMARY KEY,
+    uid BIGINT NOT NULL REFERENCES users(id),
+    tid BIGINT NOT NULL REFERENCES torrent_info(id),
+    kind INTEGER NOT NULL,
+    startTime TIMESTAMPTZ NOT NULL DEFAULT now(),
+    endTime TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX token_usage_uid_tid_idx ON token_usage(uid, tid, endTime);
@@ -0,0 +1,8 @@
+-- Add migration script here
+ALTER TABLE torrent_info
+    ADD COLUMN promotion INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN promotionStart TIMESTAMPTZ,
+    ADD COLUMN promotionEnd TIMESTAMPTZ;
+UPDATE torrent_info SET promotion = 1 WHERE free;
+ALTER TABLE torrent_info DROP COLUMN free;
+CREATE INDEX torrent_info_promotion_end_idx ON torrent_info(promotionEnd) WHERE promotion <> 0;
@@ -0,0 +1,14 @@
+-- Add migration script here
+DROP TABLE if exists site_event;
+CREATE TABLE site_event(
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    promotion INTEGER NOT NULL,
+    tag VARCHAR[],
+    startTime TIMESTAMPTZ NOT NULL,
+    endTime TIMESTAMPTZ NOT NULL,
+    operator VARCHAR(50) NOT NULL,
+    createTime TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK ( startTime < endTime )
+);
+CREATE INDEX site_event_time_idx ON site_event(startTime, endTime);
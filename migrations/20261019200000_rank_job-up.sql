@@ -0,0 +1,20 @@
+-- Add migration script here
+ALTER TABLE rank
+    ADD COLUMN ratio DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    ADD COLUMN seedtime BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE user_info ADD COLUMN rankLocked BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- messages sent by the system have no sender
+ALTER TABLE message ALTER COLUMN sender DROP NOT NULL;
+
+DROP TABLE if exists rank_log;
+CREATE TABLE rank_log(
+    id BIGSERIAL PRIMARY KEY,
+    uid BIGINT NOT NULL REFERENCES users(id),
+    fromRank INTEGER NOT NULL REFERENCES rank(id),
+    toRank INTEGER NOT NULL REFERENCES rank(id),
+    reason VARCHAR NOT NULL,
+    createTime TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX rank_log_uid_idx ON rank_log(uid);
@@ -0,0 +1,8 @@
+-- Add migration script here
+DROP TABLE if exists ratio_watch;
+CREATE TABLE ratio_watch(
+    uid BIGINT PRIMARY KEY REFERENCES users(id),
+    status INTEGER NOT NULL DEFAULT 0,
+    startTime TIMESTAMPTZ NOT NULL DEFAULT now(),
+    deadline TIMESTAMPTZ NOT NULL
+);
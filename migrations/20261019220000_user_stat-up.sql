@@ -0,0 +1,13 @@
+-- Add migration script here
+DROP TABLE if exists user_stat;
+CREATE TABLE user_stat(
+    uid BIGINT NOT NULL REFERENCES users(id),
+    day DATE NOT NULL,
+    upload BIGINT NOT NULL,
+    download BIGINT NOT NULL,
+    seeding INTEGER NOT NULL,
+    seedSize BIGINT NOT NULL,
+    bonus DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY(uid, day)
+);
+CREATE INDEX user_stat_day_idx ON user_stat(day);
@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE torrent
+    ADD COLUMN infohashV2 VARCHAR,
+    ADD COLUMN pieceLayers BYTEA;
+CREATE INDEX torrent_infohash_idx ON torrent(infohash);
+CREATE INDEX torrent_infohash_v2_idx ON torrent(infohashV2);
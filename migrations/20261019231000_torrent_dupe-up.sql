@@ -0,0 +1,24 @@
+-- Add migration script here
+DROP INDEX IF EXISTS torrent_infohash_idx;
+DROP INDEX IF EXISTS torrent_infohash_v2_idx;
+ALTER TABLE torrent_info
+    ADD COLUMN dupeOf BIGINT REFERENCES torrent_info(id),
+    ADD COLUMN dupeScore DOUBLE PRECISION;
+-- copies uploaded before infohash was unique are flagged as dupes
+-- of the oldest one for review, and left out of the unique indexes
+ALTER TABLE torrent ADD COLUMN legacyCopy BOOLEAN NOT NULL DEFAULT FALSE;
+WITH copies AS (
+    SELECT id, LEAST(
+        MIN(id) OVER (PARTITION BY infohash),
+        CASE WHEN infohashV2 IS NULL THEN id ELSE MIN(id) OVER (PARTITION BY infohashV2) END
+    ) AS origin FROM torrent
+), flagged AS (
+    UPDATE torrent SET legacyCopy = TRUE FROM copies
+    WHERE torrent.id = copies.id AND copies.origin <> copies.id
+    RETURNING torrent.id, copies.origin
+)
+UPDATE torrent_info SET dupeOf = flagged.origin, dupeScore = 1.0
+FROM flagged WHERE torrent_info.id = flagged.id;
+CREATE UNIQUE INDEX torrent_infohash_idx ON torrent(infohash) WHERE NOT legacyCopy;
+CREATE UNIQUE INDEX torrent_infohash_v2_idx ON torrent(infohashV2) WHERE NOT legacyCopy;
+CREATE INDEX torrent_length_idx ON torrent(length);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE torrent
+    ADD COLUMN fileSizes BIGINT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,24 @@
+-- Add migration script here
+DROP TABLE if exists subscription_match;
+DROP TABLE if exists subscription;
+CREATE TABLE subscription(
+    id BIGSERIAL PRIMARY KEY,
+    uid BIGINT NOT NULL REFERENCES users(id),
+    name VARCHAR NOT NULL,
+    keywords VARCHAR[] NOT NULL DEFAULT '{}',
+    tags VARCHAR[] NOT NULL DEFAULT '{}',
+    createTime TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX subscription_uid_idx ON subscription(uid);
+-- a torrent is notified once to a user even if several subscriptions match
+CREATE TABLE subscription_match(
+    uid BIGINT NOT NULL REFERENCES users(id),
+    tid BIGINT NOT NULL REFERENCES torrent_info(id),
+    sid BIGINT NOT NULL REFERENCES subscription(id) ON DELETE CASCADE,
+    matchTime TIMESTAMPTZ NOT NULL DEFAULT now(),
+    notifyTime TIMESTAMPTZ,
+    PRIMARY KEY (uid, tid)
+);
+CREATE INDEX subscription_match_pending_idx ON subscription_match(uid) WHERE notifyTime IS NULL;
+ALTER TABLE user_info
+    ADD COLUMN subscriptionDigest BOOLEAN NOT NULL DEFAULT FALSE;
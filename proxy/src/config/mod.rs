@@ -2,14 +2,31 @@ pub mod client;
 
 use client::Client;
 use config::ConfigError;
+use ipnet::IpNet;
 use lazy_static::lazy_static;
 use serde::Deserialize;
+use std::net::IpAddr;
 use std::sync::Arc;
 
 lazy_static! {
     pub(crate) static ref CONFIG: Arc<Config> = Arc::new(Config::from_env().unwrap());
 }
 
+lazy_static! {
+    /// reverse proxies whose forwarding headers are trusted
+    pub static ref TRUSTED_PROXIES: Vec<IpNet> = CONFIG
+        .trusted_proxies
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<IpNet>()
+                .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+                .expect("invalid TRUSTED_PROXIES")
+        })
+        .collect();
+}
+
 lazy_static! {
     pub static ref ALLOWED_CLIENT: Vec<Client> = vec![
         Client::UTorrent,
@@ -20,16 +37,35 @@ lazy_static! {
     ];
 }
 
+lazy_static! {
+    /// clients allowed to announce via websocket
+    pub static ref ALLOWED_WEB_CLIENT: Vec<Client> =
+        vec![Client::WebTorrent, Client::WebTorrentDesktop];
+}
+
 pub fn default_num_want() -> u16 {
     50
 }
 
+fn default_snapshot_path() -> String {
+    String::from("proxy_snapshot.json")
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub server_addr: String,
     pub tracker_addr: String,
     pub redis_uri: String,
     pub database_url: String,
+    /// where undelivered backend notifications are kept across restarts
+    #[serde(default = "default_snapshot_path")]
+    pub snapshot_path: String,
+    /// comma separated CIDRs, e.g. `127.0.0.1,10.0.0.0/8`
+    #[serde(default)]
+    pub trusted_proxies: String,
+    /// accept private or loopback addresses, useful for LAN sites
+    #[serde(default)]
+    pub allow_private_ip: bool,
 }
 
 impl Config {
@@ -1,4 +1,6 @@
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
+use bendy::encoding::{SingleItemEncoder, ToBencode};
 use deadpool::managed::PoolError;
 use deadpool_redis::redis::RedisError;
 use std::fmt::{Display, Formatter};
@@ -11,9 +13,20 @@ pub enum ProxyError {
     EncodeError,
 }
 
+impl ProxyError {
+    /// human readable reason sent back to clients
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ProxyError::RequestError(reason) => reason,
+            ProxyError::RedisError | ProxyError::PoolError => "tracker temporarily unavailable",
+            ProxyError::EncodeError => "malformed request",
+        }
+    }
+}
+
 impl Display for ProxyError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{}", self.reason())
     }
 }
 
@@ -41,11 +54,75 @@ impl From<serde_qs::Error> for ProxyError {
     }
 }
 
+impl From<serde_json::Error> for ProxyError {
+    fn from(_: serde_json::Error) -> Self {
+        Self::EncodeError
+    }
+}
+
+impl From<actix_web::Error> for ProxyError {
+    fn from(_: actix_web::Error) -> Self {
+        Self::RequestError("websocket handshake failed")
+    }
+}
+
 impl std::error::Error for ProxyError {}
 
+/// clients only understand a bencoded `failure reason`
+struct FailureResponse(&'static str);
+
+impl ToBencode for FailureResponse {
+    const MAX_DEPTH: usize = 1;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), bendy::encoding::Error> {
+        encoder.emit_dict(|mut e| e.emit_pair(b"failure reason", self.0))
+    }
+}
+
+impl ProxyError {
+    /// Response to a failed announce. BitTorrent clients ignore
+    /// the body of a non-200 response, so it is sent with 200.
+    pub fn failure_response(&self) -> HttpResponse {
+        match FailureResponse(self.reason()).to_bencode() {
+            Ok(body) => HttpResponse::Ok().body(body),
+            Err(_) => HttpResponse::InternalServerError().body(format!("Error: {:?}", self)),
+        }
+    }
+}
+
 impl ResponseError for ProxyError {
-    /// Transform error messages to Http Response.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ProxyError::RequestError(_) | ProxyError::EncodeError => StatusCode::BAD_REQUEST,
+            ProxyError::RedisError | ProxyError::PoolError => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Transform error messages to Http Response, announces
+    /// are answered by `failure_response` instead.
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::InternalServerError().body(format!("Error: {:?}", self))
+        HttpResponse::build(self.status_code()).body(self.reason())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[tokio::test]
+    async fn status_code_works() {
+        let e = ProxyError::RequestError("Torrent not registered!");
+        let resp = e.failure_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            to_bytes(resp.into_body()).await.unwrap(),
+            &b"d14:failure reason23:Torrent not registered!e"[..]
+        );
+        assert_eq!(e.error_response().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            ProxyError::PoolError.error_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
     }
 }
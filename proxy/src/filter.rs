@@ -77,11 +77,7 @@ impl Filter {
             size = cache.len();
         }
         if size > Filter::BATCH_SIZE {
-            if self.in_expand.load(Ordering::Relaxed) == false {
-                let cache = self.fetch_cache().await;
-                let mut inner = self.inner.write().await;
-                self.batch_update(inner.deref_mut(), cache);
-            }
+            self.flush().await;
         }
     }
 
@@ -93,11 +89,17 @@ impl Filter {
             size = cache.len();
         }
         if size > Filter::BATCH_SIZE {
-            if self.in_expand.load(Ordering::Relaxed) == false {
-                let cache = self.fetch_cache().await;
-                let mut inner = self.inner.write().await;
-                self.batch_update(inner.deref_mut(), cache);
-            }
+            self.flush().await;
+        }
+    }
+
+    /// commit the cache right now, unless the filter is in expand,
+    /// in which case the cache will be committed by expand.
+    pub async fn flush(&self) {
+        if self.in_expand.load(Ordering::Relaxed) == false {
+            let cache = self.fetch_cache().await;
+            let mut inner = self.inner.write().await;
+            self.batch_update(inner.deref_mut(), cache);
         }
     }
 
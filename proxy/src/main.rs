@@ -5,8 +5,26 @@ mod tracker_route;
 
 use crate::config::CONFIG;
 use actix_web::*;
+use tracker_route::notifier::Notifier;
 use tracker_route::*;
 
+/// wait for ctrl-c, or SIGTERM on unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut term = signal(SignalKind::terminate()).expect("unable to listen SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => (),
+            _ = term.recv() => (),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
 #[actix_web::main]
 pub async fn start_server() -> std::io::Result<()> {
     dotenv::dotenv().ok();
@@ -14,8 +32,14 @@ pub async fn start_server() -> std::io::Result<()> {
     println!("⭐⭐⭐⭐⭐⭐⭐⭐⭐ Initializing filter ⭐⭐⭐⭐⭐⭐⭐⭐⭐");
     let keys = get_passkey_from_db().await;
     context::CONTEXT.filter.expand(keys).await;
+    let bans = get_ip_ban_from_db().await;
+    context::CONTEXT.update_ip_bans(&bans, &[]).await;
+    let info_hashes = get_info_hash_from_db().await;
+    context::CONTEXT.update_info_hashes(info_hashes).await;
+    context::CONTEXT.notifier.start();
+    context::CONTEXT.notifier.restore(&CONFIG.snapshot_path);
     println!("⭐⭐⭐⭐⭐⭐⭐⭐ SOPT tracker is running ⭐⭐⭐⭐⭐⭐⭐⭐");
-    HttpServer::new(|| {
+    let server = HttpServer::new(|| {
         App::new()
             .wrap(middleware::Logger::new("%a \"%r\" %s %T"))
             .service(tracker_service())
@@ -23,9 +47,24 @@ pub async fn start_server() -> std::io::Result<()> {
                 web::route().to(|| async { HttpResponse::NotFound().body("Not Found") }),
             )
     })
+    .disable_signals()
     .bind(&CONFIG.tracker_addr)?
-    .run()
-    .await
+    .run();
+
+    let handle = server.handle();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        log::info!("shutting down, stop accepting announces");
+        context::CONTEXT.stop_accepting();
+        websocket::close_all().await;
+        // wait for in-flight announces
+        handle.stop(true).await;
+    });
+    server.await?;
+
+    println!("⭐⭐⭐⭐⭐⭐⭐⭐ Draining SOPT tracker ⭐⭐⭐⭐⭐⭐⭐⭐");
+    let undelivered = context::CONTEXT.notifier.drain().await;
+    Notifier::snapshot(&CONFIG.snapshot_path, &undelivered)
 }
 
 fn main() {
@@ -1,11 +1,18 @@
+use super::data::AnnounceRequestData;
+use super::notifier::Notifier;
 use crate::config::client::Client;
 use crate::config::{ALLOWED_CLIENT, CONFIG};
 use crate::error::ProxyError;
 use crate::filter::Filter;
 use deadpool::managed;
 use deadpool_redis::{Config, Connection, Runtime};
+use ipnet::IpNet;
 use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tokio::sync::RwLock;
 
 type Pool = managed::Pool<deadpool_redis::Manager, Connection>;
 
@@ -16,6 +23,12 @@ lazy_static! {
 pub struct Context {
     pub pool: Pool,
     pub filter: Filter,
+    pub notifier: Notifier,
+    pub ip_bans: RwLock<Vec<IpNet>>,
+    /// tid -> infohashes, loaded on start and pushed by backend
+    pub info_hashes: RwLock<HashMap<i64, Vec<[u8; 20]>>>,
+    // cleared once shutdown begins
+    accepting: AtomicBool,
     // TODO: monitor, LOGGER are needed
 }
 
@@ -26,19 +39,124 @@ impl Context {
             .create_pool(Some(Runtime::Tokio1))
             .expect("Create Redis Pool Failed!");
         let filter = Filter::new();
-        Context { pool, filter }
+        let notifier = Notifier::new();
+        let ip_bans = RwLock::new(vec![]);
+        let info_hashes = RwLock::new(HashMap::new());
+        let accepting = AtomicBool::new(true);
+        Context {
+            pool,
+            filter,
+            notifier,
+            ip_bans,
+            info_hashes,
+            accepting,
+        }
+    }
+
+    /// announces coming after this will be refused
+    pub fn stop_accepting(&self) {
+        self.accepting.store(false, Ordering::SeqCst);
     }
 
+    pub fn check_accepting(&self) -> Result<(), ProxyError> {
+        if !self.accepting.load(Ordering::SeqCst) {
+            return Err(ProxyError::RequestError(
+                "Tracker is shutting down, retry later.",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate an announce coming from `peer_addr`, and fix its addresses.
+    /// The connection address is checked against ip bans before `fix_ip`,
+    /// which prefers announced addresses that could be anything.
     pub async fn validation(
         &self,
-        data: &super::data::AnnounceRequestData,
-    ) -> Result<(), crate::error::ProxyError> {
-        let client = Client::new(&data.peer_id)?;
-        if !ALLOWED_CLIENT.contains(&client) {
+        data: &mut AnnounceRequestData,
+        peer_addr: Option<IpAddr>,
+        allow_private: bool,
+    ) -> Result<(), ProxyError> {
+        self.check_accepting()?;
+        if let Some(addr) = peer_addr {
+            self.check_ip_ban(&addr).await?;
+        }
+        data.fix_ip(peer_addr, allow_private)?;
+        let addrs = data
+            .ipv4
+            .map(IpAddr::V4)
+            .into_iter()
+            .chain(data.ipv6.map(IpAddr::V6));
+        self.check_peer(&data.peer_id, &data.passkey, &ALLOWED_CLIENT, addrs)
+            .await
+    }
+
+    /// checks of client, passkey and addresses, shared by http
+    /// and websocket announces
+    pub async fn check_peer(
+        &self,
+        peer_id: &str,
+        passkey: &String,
+        allowed: &[Client],
+        addrs: impl IntoIterator<Item = IpAddr>,
+    ) -> Result<(), ProxyError> {
+        let client = Client::new(peer_id)?;
+        if !allowed.contains(&client) {
             return Err(ProxyError::RequestError("Client not allowed!"));
         }
+        self.check_passkey(passkey).await?;
+        for addr in addrs {
+            self.check_ip_ban(&addr).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn check_ip_ban(&self, addr: &IpAddr) -> Result<(), ProxyError> {
+        if self
+            .ip_bans
+            .read()
+            .await
+            .iter()
+            .any(|net| net.contains(addr))
+        {
+            return Err(ProxyError::RequestError("Your address is banned!"));
+        }
+        Ok(())
+    }
+
+    /// apply changes of ip ban list, invalid ones are ignored
+    pub async fn update_ip_bans(&self, set: &[String], delete: &[String]) {
+        let parse = |s: &String| s.parse::<IpNet>().ok();
+        let delete: Vec<IpNet> = delete.iter().filter_map(parse).collect();
+        let mut bans = self.ip_bans.write().await;
+        bans.retain(|net| !delete.contains(net));
+        for net in set.iter().filter_map(parse) {
+            if !bans.contains(&net) {
+                bans.push(net);
+            }
+        }
+    }
+
+    /// set infohashes of torrents, those of a replaced torrent file are dropped
+    pub async fn update_info_hashes(&self, set: Vec<(i64, Vec<[u8; 20]>)>) {
+        self.info_hashes.write().await.extend(set);
+    }
 
-        if !self.filter.contains(&data.passkey).await {
+    /// `info_hash` must be one of torrent `tid`, see `accepted_info_hashes`
+    pub async fn check_info_hash(&self, tid: i64, info_hash: &[u8; 20]) -> Result<(), ProxyError> {
+        let found = self
+            .info_hashes
+            .read()
+            .await
+            .get(&tid)
+            .is_some_and(|hashes| hashes.contains(info_hash));
+        if !found {
+            return Err(ProxyError::RequestError("Torrent not registered!"));
+        }
+        Ok(())
+    }
+
+    pub async fn check_passkey(&self, passkey: &String) -> Result<(), ProxyError> {
+        if !self.filter.contains(passkey).await {
             return Err(ProxyError::RequestError(
                 "Passkey not found! Check your torrent please.",
             ));
@@ -46,3 +164,119 @@ impl Context {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::super::data::Event;
+    use super::*;
+    use crate::config::ALLOWED_WEB_CLIENT;
+
+    const PEER_ID: &str = "-qB4500-abcdefghijkl";
+
+    async fn context() -> Context {
+        let ctx = Context::new("redis://127.0.0.1/");
+        ctx.filter.insert("passkey".to_string()).await;
+        ctx.update_ip_bans(
+            &["1.2.3.0/24".to_string(), "2001:db8::/32".to_string()],
+            &[],
+        )
+        .await;
+        ctx
+    }
+
+    fn announce(ip: Option<&str>) -> AnnounceRequestData {
+        AnnounceRequestData {
+            peer_id: PEER_ID.to_string(),
+            port: 6881,
+            uid: 1,
+            tid: 1,
+            passkey: "passkey".to_string(),
+            ip: ip.map(|ip| ip.parse().unwrap()),
+            ipv4: None,
+            ipv6: None,
+            event: Event::Started,
+            numwant: 50,
+            upload: 0,
+            download: 0,
+            left: None,
+        }
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn banned_connection_address_is_refused() {
+        let ctx = context().await;
+        // announcing another address does not hide the connection one
+        let mut data = announce(Some("8.8.8.8"));
+        let err = ctx
+            .validation(&mut data, ip("1.2.3.4"), false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), "Your address is banned!");
+        let mut data = announce(None);
+        let err = ctx
+            .validation(&mut data, ip("2001:db8::1"), false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), "Your address is banned!");
+    }
+
+    #[tokio::test]
+    async fn banned_announced_address_is_refused() {
+        let ctx = context().await;
+        let mut data = announce(Some("1.2.3.4"));
+        let err = ctx
+            .validation(&mut data, ip("8.8.8.8"), false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), "Your address is banned!");
+
+        let mut data = announce(Some("2001:4860::8888"));
+        ctx.validation(&mut data, ip("8.8.8.8"), false)
+            .await
+            .unwrap();
+        assert_eq!(data.ipv4, Some("8.8.8.8".parse().unwrap()));
+        assert_eq!(data.ipv6, Some("2001:4860::8888".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn check_info_hash_works() {
+        let ctx = context().await;
+        ctx.update_info_hashes(vec![(1, vec![[1; 20], [2; 20]])])
+            .await;
+        ctx.check_info_hash(1, &[1; 20]).await.unwrap();
+        ctx.check_info_hash(1, &[2; 20]).await.unwrap();
+        assert!(ctx.check_info_hash(2, &[1; 20]).await.is_err());
+
+        // torrent file replaced
+        ctx.update_info_hashes(vec![(1, vec![[3; 20]])]).await;
+        let err = ctx.check_info_hash(1, &[1; 20]).await.unwrap_err();
+        assert_eq!(err.reason(), "Torrent not registered!");
+        ctx.check_info_hash(1, &[3; 20]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_peer_works() {
+        let ctx = context().await;
+        let passkey = "passkey".to_string();
+        ctx.check_peer(PEER_ID, &passkey, &ALLOWED_CLIENT, ip("8.8.8.8"))
+            .await
+            .unwrap();
+        let err = ctx
+            .check_peer(PEER_ID, &passkey, &ALLOWED_WEB_CLIENT, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), "Client not allowed!");
+        let err = ctx
+            .check_peer(PEER_ID, &"unknown".to_string(), &ALLOWED_CLIENT, None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.reason(),
+            "Passkey not found! Check your torrent please."
+        );
+    }
+}
@@ -1,13 +1,17 @@
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
+use super::ip::is_public;
+use crate::error::ProxyError;
 use bendy::encoding;
 use deadpool_redis::redis::{cmd, Cmd, Value};
+use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
 pub struct AnnounceRequestData {
-    // deprecated
-    // pub info_hash: Vec<u8>,
+    // swarms are keyed by tid, so announces of a hybrid torrent by either
+    // sha1 or truncated sha256 infohash join one swarm. `info_hash` is
+    // raw bytes, see `parse_info_hash`
     pub peer_id: String,
     pub port: u16,
     pub uid: i64,
@@ -22,29 +26,45 @@ pub struct AnnounceRequestData {
     pub numwant: u16,
     pub upload: i64,
     pub download: i64,
+    pub left: Option<i64>,
 }
 
 impl AnnounceRequestData {
-    pub fn fix_ip(&mut self, peer_addr: Option<IpAddr>) {
-        let mut true_v4 = None;
-        let mut true_v6 = None;
-        if let Some(ip) = self.ip {
-            match ip {
-                IpAddr::V4(v4) => true_v4 = self.ipv4.or(Some(v4)),
-                IpAddr::V6(v6) => true_v6 = self.ipv6.or(Some(v6)),
-            }
+    /// Determine the addresses to announce. Announced addresses come
+    /// first, and then the connection address.
+    /// Non-public announced addresses are rejected unless allowed,
+    /// while a non-public connection address is just ignored.
+    pub fn fix_ip(
+        &mut self,
+        peer_addr: Option<IpAddr>,
+        allow_private: bool,
+    ) -> Result<(), ProxyError> {
+        let mut true_v4 = self.ipv4;
+        let mut true_v6 = self.ipv6;
+        match self.ip {
+            Some(IpAddr::V4(v4)) => true_v4 = true_v4.or(Some(v4)),
+            Some(IpAddr::V6(v6)) => true_v6 = true_v6.or(Some(v6)),
+            None => (),
+        }
+        let mut announced = true_v4
+            .map(IpAddr::V4)
+            .into_iter()
+            .chain(true_v6.map(IpAddr::V6));
+        if !allow_private && announced.any(|ip| !is_public(&ip)) {
+            return Err(ProxyError::RequestError("Private address not allowed!"));
         }
-        if let Some(ip) = peer_addr {
+        if let Some(ip) = peer_addr.filter(|ip| allow_private || is_public(ip)) {
             match ip {
                 IpAddr::V4(v4) => true_v4 = true_v4.or(Some(v4)),
                 IpAddr::V6(v6) => true_v6 = true_v6.or(Some(v6)),
             }
         }
         if true_v4.is_none() && true_v6.is_none() {
-            panic!("unable to detect connection address");
+            return Err(ProxyError::RequestError("Unable to detect your address!"));
         }
         self.ipv4 = true_v4;
         self.ipv6 = true_v6;
+        Ok(())
     }
 
     pub fn generate_announce_cmd(&self) -> Cmd {
@@ -68,6 +88,31 @@ impl AnnounceRequestData {
     }
 }
 
+/// `info_hash` is percent encoded raw bytes, which is not valid utf-8
+/// in general, so it is parsed from the query string by hand.
+pub fn parse_info_hash(query: &str) -> Result<[u8; 20], ProxyError> {
+    let value = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("info_hash="))
+        .ok_or(ProxyError::RequestError("Missing info_hash!"))?;
+    let bytes: Vec<u8> = percent_decode_str(value).collect();
+    bytes
+        .try_into()
+        .map_err(|_| ProxyError::RequestError("Invalid info_hash!"))
+}
+
+/// Infohashes a torrent can be announced by, given as hex stored in
+/// database. `infohash` is sha1 one, or truncated sha256 one of v2 only
+/// torrents, and a hybrid torrent is also announced by its truncated
+/// sha256 infohash.
+pub fn accepted_info_hashes(infohash: &str, infohash_v2: Option<&str>) -> Vec<[u8; 20]> {
+    let truncated = infohash_v2.and_then(|v2| v2.get(..40));
+    std::iter::once(infohash)
+        .chain(truncated)
+        .filter_map(|hash| hex::decode(hash).ok()?.try_into().ok())
+        .collect()
+}
+
 #[derive(Deserialize, Debug, Copy, Clone)]
 pub enum Event {
     Started = 0,
@@ -92,41 +137,82 @@ impl ToString for Event {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct AnnounceBypassData {
     uid: i64,
     tid: i64,
     upload: i64,
     download: i64,
+    // tells backend whether peer is seeding
+    #[serde(default)]
+    left: Option<i64>,
     action: Option<Action>,
+    // unix seconds the announce is received, so that seeding time is
+    // credited as of then even if the notification is delivered late
+    #[serde(default)]
+    time: Option<i64>,
 }
 
-impl From<AnnounceRequestData> for AnnounceBypassData {
-    fn from(t: AnnounceRequestData) -> Self {
-        let action = match t.event {
-            Event::Started => Action::Start,
-            Event::Completed => Action::Complete,
-            Event::Stopped => Action::Stop,
-        };
-
+impl AnnounceBypassData {
+    pub fn new(
+        uid: i64,
+        tid: i64,
+        upload: i64,
+        download: i64,
+        left: Option<i64>,
+        event: Option<Event>,
+        time: i64,
+    ) -> Self {
         Self {
-            uid: t.uid,
-            tid: t.tid,
-            upload: t.upload,
-            download: t.download,
-            action: Some(action),
+            uid,
+            tid,
+            upload,
+            download,
+            left,
+            action: event.map(Action::from),
+            time: Some(time),
         }
     }
 }
 
+impl From<AnnounceRequestData> for AnnounceBypassData {
+    fn from(t: AnnounceRequestData) -> Self {
+        Self::new(
+            t.uid,
+            t.tid,
+            t.upload,
+            t.download,
+            t.left,
+            Some(t.event),
+            unix_now(),
+        )
+    }
+}
+
+pub fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
 #[repr(C)]
-#[derive(Serialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 enum Action {
     Start = 0,
     Complete,
     Stop,
 }
 
+impl From<Event> for Action {
+    fn from(e: Event) -> Self {
+        match e {
+            Event::Started => Action::Start,
+            Event::Completed => Action::Complete,
+            Event::Stopped => Action::Stop,
+        }
+    }
+}
+
 pub struct AnnounceResponseData {
     interval: i64,
     peers: Vec<u8>,
@@ -175,3 +261,47 @@ pub struct UpdateFilterCommand {
     pub set: Option<String>,
     pub delete: Option<String>,
 }
+
+#[derive(Deserialize)]
+pub struct UpdateIpBanCommand {
+    #[serde(default)]
+    pub set: Vec<String>,
+    #[serde(default)]
+    pub delete: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateInfoHashCommand {
+    pub tid: i64,
+    pub infohash: String,
+    pub infohash_v2: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_info_hash_works() {
+        let query = "passkey=key&info_hash=%124Vx%9A%BC%DE%F1%23Eg%89%AB%CD%EF%124Vx%9A&port=6881";
+        assert_eq!(
+            hex::encode(parse_info_hash(query).unwrap()),
+            "123456789abcdef123456789abcdef123456789a"
+        );
+        assert!(parse_info_hash("passkey=key&port=6881").is_err());
+        assert!(parse_info_hash("info_hash=%12%34").is_err());
+    }
+
+    #[test]
+    fn accepted_info_hashes_works() {
+        let v1 = "0fd4e406fb53d7070082ac352e818ab62008eb07";
+        let v2 = "7c3f3e45a1b0d2c8e9f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c";
+        assert_eq!(
+            accepted_info_hashes(v1, None),
+            vec![<[u8; 20]>::try_from(hex::decode(v1).unwrap()).unwrap()]
+        );
+        let hashes = accepted_info_hashes(v1, Some(v2));
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hex::encode(hashes[1]), &v2[..40]);
+    }
+}
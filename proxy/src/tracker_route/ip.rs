@@ -0,0 +1,192 @@
+//! Detect the real address of a client.
+//!
+//! Behind a reverse proxy the peer address is always the proxy itself,
+//! so forwarding headers are honored, but only when the connection
+//! comes from a trusted proxy. Otherwise anyone could fake its address.
+
+use actix_web::http::header::HeaderMap;
+use ipnet::IpNet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+fn is_trusted(ip: &IpAddr, trusted: &[IpNet]) -> bool {
+    trusted.iter().any(|net| net.contains(ip))
+}
+
+/// strip quotes, brackets and port of a forwarded node
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        // [2001:db8::1]:8080
+        return rest.split(']').next()?.parse().ok();
+    }
+    // 1.2.3.4:8080, unknown or obfuscated ones are ignored
+    node.rsplit_once(':')?
+        .0
+        .parse::<Ipv4Addr>()
+        .ok()
+        .map(IpAddr::V4)
+}
+
+/// `Forwarded: for=192.0.2.60;proto=http, for="[2001:db8::1]"`
+fn parse_forwarded(value: &str) -> Vec<IpAddr> {
+    value
+        .split(',')
+        .flat_map(|element| element.split(';'))
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+        .filter_map(|(_, node)| parse_node(node))
+        .collect()
+}
+
+/// `X-Forwarded-For: client, proxy1, proxy2`
+fn parse_forwarded_for(value: &str) -> Vec<IpAddr> {
+    value.split(',').filter_map(parse_node).collect()
+}
+
+/// Find the client address from peer address and headers.
+///
+/// Headers are tried in order of `Forwarded`, `X-Forwarded-For` and
+/// `X-Real-IP`. The chain is walked from right to left, the first
+/// untrusted address is the client.
+pub fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[IpNet]) -> Option<IpAddr> {
+    let peer = peer?;
+    if !is_trusted(&peer, trusted) {
+        return Some(peer);
+    }
+    let header = |name: &str| {
+        headers
+            .get_all(name)
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let mut chain = parse_forwarded(&header("forwarded"));
+    if chain.is_empty() {
+        chain = parse_forwarded_for(&header("x-forwarded-for"));
+    }
+    if chain.is_empty() {
+        chain = parse_forwarded_for(&header("x-real-ip"));
+    }
+    chain
+        .iter()
+        .rev()
+        .find(|ip| !is_trusted(ip, trusted))
+        .or_else(|| chain.first())
+        .copied()
+        .or(Some(peer))
+}
+
+/// private, loopback, link-local and alike are not reachable by other peers
+pub fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(&v4),
+            None => is_public_v6(v6),
+        },
+    }
+}
+
+fn is_public_v4(ip: &Ipv4Addr) -> bool {
+    let shared = ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64;
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || shared)
+}
+
+fn is_public_v6(ip: &Ipv6Addr) -> bool {
+    let segment = ip.segments()[0];
+    let unique_local = (segment & 0xfe00) == 0xfc00;
+    let link_local = (segment & 0xffc0) == 0xfe80;
+    !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || unique_local || link_local)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (k, v) in pairs {
+            map.append(HeaderName::from_static(k), HeaderValue::from_static(v));
+        }
+        map
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_headers() {
+        let trusted = vec!["10.0.0.0/8".parse().unwrap()];
+        let h = headers(&[("x-forwarded-for", "8.8.8.8")]);
+        assert_eq!(
+            client_ip(Some(ip("1.1.1.1")), &h, &trusted),
+            Some(ip("1.1.1.1"))
+        );
+    }
+
+    #[test]
+    fn forwarded_for_chain_works() {
+        let trusted = vec!["10.0.0.0/8".parse().unwrap()];
+        let h = headers(&[("x-forwarded-for", "6.6.6.6, 8.8.8.8, 10.0.0.2")]);
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), &h, &trusted),
+            Some(ip("8.8.8.8"))
+        );
+        let h = headers(&[("x-real-ip", "8.8.4.4")]);
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), &h, &trusted),
+            Some(ip("8.8.4.4"))
+        );
+        let h = headers(&[]);
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), &h, &trusted),
+            Some(ip("10.0.0.1"))
+        );
+    }
+
+    #[test]
+    fn forwarded_header_works() {
+        let trusted = vec!["127.0.0.1/32".parse().unwrap()];
+        let h = headers(&[
+            (
+                "forwarded",
+                "for=\"[2001:db8::1]:4711\";proto=https, for=unknown",
+            ),
+            ("x-forwarded-for", "8.8.8.8"),
+        ]);
+        assert_eq!(
+            client_ip(Some(ip("127.0.0.1")), &h, &trusted),
+            Some(ip("2001:db8::1"))
+        );
+        let h = headers(&[("forwarded", "For=192.0.2.60:80;by=127.0.0.1")]);
+        assert_eq!(
+            client_ip(Some(ip("127.0.0.1")), &h, &trusted),
+            Some(ip("192.0.2.60"))
+        );
+    }
+
+    #[test]
+    fn public_address_works() {
+        assert!(is_public(&ip("8.8.8.8")));
+        assert!(is_public(&ip("2001:4860::8888")));
+        assert!(!is_public(&ip("192.168.1.1")));
+        assert!(!is_public(&ip("127.0.0.1")));
+        assert!(!is_public(&ip("169.254.0.1")));
+        assert!(!is_public(&ip("100.64.0.1")));
+        assert!(!is_public(&ip("::1")));
+        assert!(!is_public(&ip("fd00::1")));
+        assert!(!is_public(&ip("fe80::1")));
+        assert!(!is_public(&ip("::ffff:10.0.0.1")));
+    }
+}
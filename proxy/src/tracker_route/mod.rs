@@ -1,12 +1,18 @@
 pub(crate) mod context;
 mod data;
+mod ip;
+pub(crate) mod notifier;
+pub(crate) mod websocket;
 
-use crate::config::CONFIG;
+use crate::config::{CONFIG, TRUSTED_PROXIES};
 use crate::error::ProxyError;
 use actix_web::*;
 use bendy::encoding::ToBencode;
 use context::CONTEXT;
-use data::{AnnounceBypassData, AnnounceRequestData, AnnounceResponseData, UpdateFilterCommand};
+use data::{
+    AnnounceBypassData, AnnounceRequestData, AnnounceResponseData, UpdateFilterCommand,
+    UpdateInfoHashCommand, UpdateIpBanCommand,
+};
 use deadpool_redis::redis::Value;
 
 type ProxyResult = Result<HttpResponse, ProxyError>;
@@ -26,14 +32,59 @@ pub async fn get_passkey_from_db() -> Vec<String> {
     rets
 }
 
+pub async fn get_ip_ban_from_db() -> Vec<String> {
+    let client = sqlx::PgPool::connect(&CONFIG.database_url)
+        .await
+        .expect("unable to connect to database");
+
+    let rets: Vec<String> = sqlx::query!("SELECT cidr FROM ip_ban;")
+        .fetch_all(&client)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.cidr)
+        .collect();
+    rets
+}
+
+/// infohashes of all torrents, later changes are pushed by backend
+pub async fn get_info_hash_from_db() -> Vec<(i64, Vec<[u8; 20]>)> {
+    let client = sqlx::PgPool::connect(&CONFIG.database_url)
+        .await
+        .expect("unable to connect to database");
+
+    let rets: Vec<(i64, Vec<[u8; 20]>)> =
+        sqlx::query!("SELECT id, infohash, infohashV2 FROM torrent;")
+            .fetch_all(&client)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| {
+                let hashes = data::accepted_info_hashes(&r.infohash, r.infohashv2.as_deref());
+                (r.id, hashes)
+            })
+            .collect();
+    rets
+}
+
 #[get("/announce")]
-async fn announce(
-    web::Query(mut q): web::Query<AnnounceRequestData>,
-    req: HttpRequest,
-) -> ProxyResult {
-    let peer_ip = req.peer_addr().map(|addr| addr.ip());
-    CONTEXT.validation(&q).await?;
-    q.fix_ip(peer_ip);
+async fn announce(q: web::Query<AnnounceRequestData>, req: HttpRequest) -> HttpResponse {
+    handle_announce(q.into_inner(), &req)
+        .await
+        .unwrap_or_else(|e| e.failure_response())
+}
+
+async fn handle_announce(mut q: AnnounceRequestData, req: &HttpRequest) -> ProxyResult {
+    let peer_ip = ip::client_ip(
+        req.peer_addr().map(|addr| addr.ip()),
+        req.headers(),
+        &TRUSTED_PROXIES,
+    );
+    CONTEXT
+        .validation(&mut q, peer_ip, CONFIG.allow_private_ip)
+        .await?;
+    let info_hash = data::parse_info_hash(req.query_string())?;
+    CONTEXT.check_info_hash(q.tid, &info_hash).await?;
 
     let mut cxn = CONTEXT.pool.get().await?;
     let cmd = q.generate_announce_cmd();
@@ -41,22 +92,18 @@ async fn announce(
     let response = AnnounceResponseData::from(t);
     let x = response.to_bencode()?;
 
-    let req = AnnounceBypassData::from(q);
-    let req = serde_qs::to_string(&req)?;
-    let addr = format!(
-        "http://{}/api/tracker/get_announce?{}",
-        CONFIG.server_addr, req
-    );
-    let resp = reqwest::get(&addr)
-        .await
-        .map_err(|_| ProxyError::RequestError("bypass to backend failed"))?;
-    if !resp.status().is_success() {
-        return Err(ProxyError::RequestError("bypass to backend failed"));
-    }
+    bypass_to_backend(AnnounceBypassData::from(q));
 
     Ok(HttpResponse::Ok().body(x))
 }
 
+/// record the announce in backend, so that upload, download
+/// and seeding status can be updated. It is queued and sent
+/// in background, see `Notifier`.
+fn bypass_to_backend(data: AnnounceBypassData) {
+    CONTEXT.notifier.notify(data);
+}
+
 #[post("update_filter")]
 async fn update_filter(query: web::Json<UpdateFilterCommand>) -> ProxyResult {
     let query = query.into_inner();
@@ -77,8 +124,27 @@ async fn update_filter(query: web::Json<UpdateFilterCommand>) -> ProxyResult {
     Ok(HttpResponse::Ok().finish())
 }
 
+#[post("update_ip_ban")]
+async fn update_ip_ban(query: web::Json<UpdateIpBanCommand>) -> ProxyResult {
+    CONTEXT.update_ip_bans(&query.set, &query.delete).await;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("update_info_hash")]
+async fn update_info_hash(query: web::Json<UpdateInfoHashCommand>) -> ProxyResult {
+    let hashes = data::accepted_info_hashes(&query.infohash, query.infohash_v2.as_deref());
+    if hashes.is_empty() {
+        return Err(ProxyError::RequestError("Invalid info_hash!"));
+    }
+    CONTEXT.update_info_hashes(vec![(query.tid, hashes)]).await;
+    Ok(HttpResponse::Ok().finish())
+}
+
 pub fn tracker_service() -> Scope {
     web::scope("/tracker")
         .service(announce)
+        .service(websocket::ws_announce)
         .service(update_filter)
+        .service(update_ip_ban)
+        .service(update_info_hash)
 }
@@ -0,0 +1,325 @@
+use super::data::AnnounceBypassData;
+use crate::config::CONFIG;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// Notifications to backend are sent in background, so that
+/// announces will never wait for (or fail with) the backend.
+///
+/// Once backend is unreachable, notifications are kept and retried
+/// every minute. On shutdown the queue is drained and what is still
+/// undelivered will be written to a snapshot, which is replayed
+/// on next start.
+pub struct Notifier {
+    sender: Mutex<Option<UnboundedSender<AnnounceBypassData>>>,
+    receiver: Mutex<Option<UnboundedReceiver<AnnounceBypassData>>>,
+    worker: Mutex<Option<JoinHandle<Vec<AnnounceBypassData>>>>,
+}
+
+impl Notifier {
+    const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+    const MAX_PENDING: usize = 1 << 20;
+
+    pub fn new() -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Self {
+            sender: Mutex::new(Some(sender)),
+            receiver: Mutex::new(Some(receiver)),
+            worker: Mutex::new(None),
+        }
+    }
+
+    /// spawn the background worker, only the first call works
+    pub fn start(&self) {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .expect("unable to build http client");
+        self.spawn(move |data| send(client.clone(), data), Self::RETRY_INTERVAL);
+    }
+
+    fn spawn<F, Fut>(&self, send: F, retry_interval: Duration)
+    where
+        F: Fn(AnnounceBypassData) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), AnnounceBypassData>> + Send + 'static,
+    {
+        if let Some(receiver) = self.receiver.lock().unwrap().take() {
+            let worker = tokio::spawn(work(receiver, send, retry_interval));
+            *self.worker.lock().unwrap() = Some(worker);
+        }
+    }
+
+    pub fn notify(&self, data: AnnounceBypassData) {
+        match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => {
+                if sender.send(data).is_err() {
+                    log::error!("notifier worker has gone");
+                }
+            }
+            None => log::warn!("notifier closed, drop {:?}", data),
+        }
+    }
+
+    /// stop accepting notifications and wait until all queued
+    /// ones are handled, returns those undelivered.
+    pub async fn drain(&self) -> Vec<AnnounceBypassData> {
+        self.sender.lock().unwrap().take();
+        let worker = self.worker.lock().unwrap().take();
+        match worker {
+            Some(worker) => worker.await.unwrap_or_default(),
+            None => vec![],
+        }
+    }
+
+    /// Re-send notifications left by last shutdown in snapshot `path`.
+    /// A broken snapshot is renamed to `<path>.broken` instead of
+    /// removed, so that it can be repaired by hand.
+    pub fn restore(&self, path: &str) {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                log::error!("unable to read snapshot {}: {}", path, e);
+                return;
+            }
+        };
+        match serde_json::from_slice::<Vec<AnnounceBypassData>>(&content) {
+            Ok(pending) => {
+                log::info!("restore {} notifications from snapshot", pending.len());
+                pending.into_iter().for_each(|data| self.notify(data));
+                if let Err(e) = std::fs::remove_file(path) {
+                    log::error!("unable to remove snapshot {}: {}", path, e);
+                }
+            }
+            Err(e) => {
+                let broken = format!("{}.broken", path);
+                log::error!("broken snapshot {}: {}, moved to {}", path, e, broken);
+                if let Err(e) = std::fs::rename(path, &broken) {
+                    log::error!("unable to move snapshot {}: {}", path, e);
+                }
+            }
+        }
+    }
+
+    /// write undelivered notifications to snapshot `path`
+    pub fn snapshot(path: &str, pending: &[AnnounceBypassData]) -> std::io::Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let content = serde_json::to_vec(pending)?;
+        std::fs::write(path, content)?;
+        log::info!(
+            "{} undelivered notifications saved to {}",
+            pending.len(),
+            path
+        );
+        Ok(())
+    }
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `send` gives the notification back if it should be retried
+async fn work<F, Fut>(
+    mut receiver: UnboundedReceiver<AnnounceBypassData>,
+    send: F,
+    retry_interval: Duration,
+) -> Vec<AnnounceBypassData>
+where
+    F: Fn(AnnounceBypassData) -> Fut,
+    Fut: Future<Output = Result<(), AnnounceBypassData>>,
+{
+    let mut failed: VecDeque<AnnounceBypassData> = VecDeque::new();
+    let mut retry = tokio::time::interval(retry_interval);
+
+    loop {
+        tokio::select! {
+            data = receiver.recv() => match data {
+                Some(data) => {
+                    // keep order once backend is down
+                    let undelivered = if failed.is_empty() {
+                        send(data).await.err()
+                    } else {
+                        Some(data)
+                    };
+                    if let Some(data) = undelivered {
+                        if failed.len() >= Notifier::MAX_PENDING {
+                            log::error!("too many undelivered, drop {:?}", failed.pop_front());
+                        }
+                        failed.push_back(data);
+                    }
+                }
+                // all senders dropped and queue is empty
+                None => break,
+            },
+            _ = retry.tick() => {
+                while let Some(data) = failed.pop_front() {
+                    if let Err(data) = send(data).await {
+                        failed.push_front(data);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    failed.into()
+}
+
+async fn send(client: reqwest::Client, data: AnnounceBypassData) -> Result<(), AnnounceBypassData> {
+    let req = match serde_qs::to_string(&data) {
+        Ok(req) => req,
+        Err(e) => {
+            log::error!("unable to encode {:?}: {}", data, e);
+            return Ok(());
+        }
+    };
+    let addr = format!(
+        "http://{}/api/tracker/get_announce?{}",
+        CONFIG.server_addr, req
+    );
+    match client.get(&addr).send().await {
+        Ok(resp) if resp.status().is_success() => Ok(()),
+        Ok(resp) => {
+            // backend rejected it, retry makes no sense
+            log::warn!("backend rejected {:?}: {}", data, resp.status());
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("bypass to backend failed: {}", e);
+            Err(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    const RETRY: Duration = Duration::from_millis(20);
+
+    fn data(uid: i64) -> AnnounceBypassData {
+        AnnounceBypassData::new(uid, 1, 1024, 0, Some(0), None, 1700000000)
+    }
+
+    /// a backend recording what it received, unreachable while `down`
+    #[derive(Clone, Default)]
+    struct Backend {
+        down: Arc<AtomicBool>,
+        received: Arc<Mutex<Vec<AnnounceBypassData>>>,
+    }
+
+    impl Backend {
+        fn send(
+            &self,
+        ) -> impl Fn(AnnounceBypassData) -> std::future::Ready<Result<(), AnnounceBypassData>>
+        {
+            let backend = self.clone();
+            move |data| {
+                if backend.down.load(Ordering::SeqCst) {
+                    return std::future::ready(Err(data));
+                }
+                backend.received.lock().unwrap().push(data);
+                std::future::ready(Ok(()))
+            }
+        }
+
+        fn received(&self) -> Vec<AnnounceBypassData> {
+            std::mem::take(&mut *self.received.lock().unwrap())
+        }
+    }
+
+    fn snapshot_path(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("sopt_proxy_{}_{}.json", name, std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[tokio::test]
+    async fn queue_works() {
+        let backend = Backend::default();
+        let notifier = Notifier::new();
+        notifier.spawn(backend.send(), RETRY);
+        for uid in 1..=3 {
+            notifier.notify(data(uid));
+        }
+        assert!(notifier.drain().await.is_empty());
+        assert_eq!(backend.received(), vec![data(1), data(2), data(3)]);
+    }
+
+    // the clock is paused and only advances when all tasks are idle,
+    // so the worker has retried exactly as the sleeps say
+    #[tokio::test(start_paused = true)]
+    async fn retry_works() {
+        let backend = Backend::default();
+        backend.down.store(true, Ordering::SeqCst);
+        let notifier = Notifier::new();
+        notifier.spawn(backend.send(), RETRY);
+        notifier.notify(data(1));
+        notifier.notify(data(2));
+        tokio::time::sleep(RETRY * 3).await;
+        assert!(backend.received().is_empty());
+
+        backend.down.store(false, Ordering::SeqCst);
+        tokio::time::sleep(RETRY * 3).await;
+        // order is kept across retries
+        notifier.notify(data(3));
+        assert!(notifier.drain().await.is_empty());
+        assert_eq!(backend.received(), vec![data(1), data(2), data(3)]);
+    }
+
+    #[tokio::test]
+    async fn drain_works() {
+        let backend = Backend::default();
+        backend.down.store(true, Ordering::SeqCst);
+        let notifier = Notifier::new();
+        notifier.spawn(backend.send(), RETRY);
+        notifier.notify(data(1));
+        notifier.notify(data(2));
+        assert_eq!(notifier.drain().await, vec![data(1), data(2)]);
+        // closed notifier drops notifications
+        notifier.notify(data(3));
+        assert!(notifier.drain().await.is_empty());
+        assert!(backend.received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn snapshot_round_trip_works() {
+        let path = snapshot_path("snapshot");
+        Notifier::snapshot(&path, &[data(1), data(2)]).unwrap();
+
+        let backend = Backend::default();
+        let notifier = Notifier::new();
+        notifier.restore(&path);
+        assert!(std::fs::metadata(&path).is_err());
+        notifier.spawn(backend.send(), RETRY);
+        assert!(notifier.drain().await.is_empty());
+        assert_eq!(backend.received(), vec![data(1), data(2)]);
+
+        // nothing to save, nothing to restore
+        Notifier::snapshot(&path, &[]).unwrap();
+        assert!(std::fs::metadata(&path).is_err());
+        Notifier::new().restore(&path);
+    }
+
+    #[test]
+    fn broken_snapshot_is_kept() {
+        let path = snapshot_path("broken");
+        let broken = format!("{}.broken", path);
+        std::fs::write(&path, b"[{\"uid\":").unwrap();
+
+        Notifier::new().restore(&path);
+        assert!(std::fs::metadata(&path).is_err());
+        assert_eq!(std::fs::read(&broken).unwrap(), b"[{\"uid\":");
+        std::fs::remove_file(&broken).unwrap();
+    }
+}
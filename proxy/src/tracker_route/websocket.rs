@@ -0,0 +1,560 @@
+//! WebTorrent tracker protocol over WebSocket.
+//!
+//! Browsers cannot open TCP/UDP connections, so peers find each other
+//! by exchanging WebRTC offers and answers through the tracker. We only
+//! relay them between peers of the same torrent and never look inside.
+//! Swarms are kept in memory here instead of redis, as every peer needs
+//! a live socket to receive offers anyway.
+
+use super::*;
+use crate::config::ALLOWED_WEB_CLIENT;
+use actix_ws::{Message, Session};
+use context::Context;
+use data::{unix_now, Event};
+use lazy_static::lazy_static;
+use rand::seq::IteratorRandom;
+use serde::Deserialize;
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use tokio::sync::Mutex;
+
+/// WebTorrent clients re-announce much more often than others
+const WS_INTERVAL: i64 = 120;
+/// at most this many offers will be relayed per announce
+const MAX_OFFERS: usize = 10;
+
+/// passkey, tid and uid are carried in the websocket url,
+/// just like http announce.
+#[derive(Deserialize, Debug)]
+pub struct WsAuth {
+    pub passkey: String,
+    pub uid: i64,
+    pub tid: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct WsOffer {
+    offer: Json,
+    offer_id: String,
+}
+
+/// Both announces and answers use `announce` action.
+/// 1. an announce contains `offers` and stats
+/// 2. an answer contains `answer`, `offer_id` and `to_peer_id`
+#[derive(Deserialize, Debug)]
+struct WsRequest {
+    action: String,
+    info_hash: String,
+    peer_id: String,
+    #[serde(default)]
+    uploaded: i64,
+    #[serde(default)]
+    downloaded: i64,
+    left: Option<i64>,
+    event: Option<String>,
+    offers: Option<Vec<WsOffer>>,
+    answer: Option<Json>,
+    offer_id: Option<String>,
+    to_peer_id: Option<String>,
+}
+
+/// `S` is the socket of a peer, generic so that swarms can be tested
+/// without a live connection.
+struct WsPeer<S = Session> {
+    session: S,
+    uid: i64,
+    seeding: bool,
+    // last reported stats, backend wants increments
+    uploaded: i64,
+    downloaded: i64,
+}
+
+/// peer_id -> peer
+type Swarm<S = Session> = HashMap<String, WsPeer<S>>;
+
+lazy_static! {
+    /// tid -> swarm
+    static ref SWARMS: Mutex<HashMap<i64, Swarm>> = Mutex::new(HashMap::new());
+}
+
+#[get("/ws")]
+pub async fn ws_announce(
+    web::Query(auth): web::Query<WsAuth>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> ProxyResult {
+    let peer_addr = ip::client_ip(
+        req.peer_addr().map(|addr| addr.ip()),
+        req.headers(),
+        &TRUSTED_PROXIES,
+    );
+    CONTEXT.check_accepting()?;
+    CONTEXT.check_passkey(&auth.passkey).await?;
+    if let Some(addr) = peer_addr {
+        CONTEXT.check_ip_ban(&addr).await?;
+    }
+    let (response, session, stream) = actix_ws::handle(&req, body)?;
+    actix_web::rt::spawn(serve(auth, peer_addr, session, stream));
+    Ok(response)
+}
+
+async fn serve(
+    auth: WsAuth,
+    peer_addr: Option<IpAddr>,
+    mut session: Session,
+    mut stream: actix_ws::MessageStream,
+) {
+    // a socket stands for one peer of one torrent
+    let mut joined: Option<String> = None;
+    while let Some(Ok(msg)) = stream.recv().await {
+        match msg {
+            Message::Text(text) => {
+                if let Err(e) = handle_request(&auth, peer_addr, &session, &text, &mut joined).await
+                {
+                    let failure = json!({
+                        "action": "announce",
+                        "failure reason": e.reason(),
+                    });
+                    if session.text(failure.to_string()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            Message::Ping(bytes) => {
+                if session.pong(&bytes).await.is_err() {
+                    break;
+                }
+            }
+            Message::Close(_) => break,
+            _ => (),
+        }
+    }
+    if let Some(peer_id) = joined {
+        leave(auth.tid, &peer_id).await;
+    }
+    let _ = session.close(None).await;
+}
+
+/// WebTorrent sends `info_hash` as a binary string, a char for each byte
+fn ws_info_hash(info_hash: &str) -> Result<[u8; 20], ProxyError> {
+    info_hash
+        .chars()
+        .map(|c| u8::try_from(c).ok())
+        .collect::<Option<Vec<u8>>>()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(ProxyError::RequestError("Invalid info_hash!"))
+}
+
+fn parse_request(text: &str) -> Result<WsRequest, ProxyError> {
+    let req: WsRequest = serde_json::from_str(text)?;
+    if req.action != "announce" {
+        return Err(ProxyError::RequestError("Only announce is supported!"));
+    }
+    Ok(req)
+}
+
+fn parse_event(event: Option<&str>) -> Option<Event> {
+    match event {
+        Some("started") => Some(Event::Started),
+        Some("completed") => Some(Event::Completed),
+        Some("stopped") => Some(Event::Stopped),
+        _ => None,
+    }
+}
+
+/// Every message is validated the same as an http announce, as bans
+/// and passkeys might change while the socket is open.
+async fn validation(
+    ctx: &Context,
+    auth: &WsAuth,
+    peer_addr: Option<IpAddr>,
+    req: &WsRequest,
+) -> Result<(), ProxyError> {
+    ctx.check_accepting()?;
+    ctx.check_peer(&req.peer_id, &auth.passkey, &ALLOWED_WEB_CLIENT, peer_addr)
+        .await?;
+    ctx.check_info_hash(auth.tid, &ws_info_hash(&req.info_hash)?)
+        .await
+}
+
+/// what an announce changed in its swarm
+struct Announced<S> {
+    // increments since last announce
+    upload: i64,
+    download: i64,
+    complete: usize,
+    incomplete: usize,
+    // peers to receive offers
+    receivers: Vec<S>,
+}
+
+/// Join or update the announcing peer, and pick receivers for its offers.
+/// A seeder needs no offer from another seeder, and a stopped peer
+/// leaves at once.
+fn announce_in_swarm<S: Clone>(
+    swarm: &mut Swarm<S>,
+    uid: i64,
+    session: &S,
+    req: &WsRequest,
+    event: Option<Event>,
+) -> Result<Announced<S>, ProxyError> {
+    let seeding = req.left == Some(0);
+    let (upload, download) = match swarm.get_mut(&req.peer_id) {
+        Some(peer) => {
+            if peer.uid != uid {
+                return Err(ProxyError::RequestError("Peer id already taken!"));
+            }
+            let delta = (
+                (req.uploaded - peer.uploaded).max(0),
+                (req.downloaded - peer.downloaded).max(0),
+            );
+            // might be a reconnection
+            peer.session = session.clone();
+            peer.seeding = seeding;
+            peer.uploaded = req.uploaded;
+            peer.downloaded = req.downloaded;
+            delta
+        }
+        None => {
+            swarm.insert(
+                req.peer_id.clone(),
+                WsPeer {
+                    session: session.clone(),
+                    uid,
+                    seeding,
+                    uploaded: req.uploaded,
+                    downloaded: req.downloaded,
+                },
+            );
+            (req.uploaded, req.downloaded)
+        }
+    };
+
+    let complete = swarm.values().filter(|p| p.seeding).count();
+    let incomplete = swarm.len() - complete;
+    let offers = req.offers.as_ref().map_or(0, Vec::len);
+    let receivers = swarm
+        .iter()
+        .filter(|(id, p)| *id != &req.peer_id && !(seeding && p.seeding))
+        .map(|(_, p)| p.session.clone())
+        .choose_multiple(&mut rand::thread_rng(), offers.min(MAX_OFFERS));
+    if matches!(event, Some(Event::Stopped)) {
+        swarm.remove(&req.peer_id);
+    }
+    Ok(Announced {
+        upload,
+        download,
+        complete,
+        incomplete,
+        receivers,
+    })
+}
+
+async fn handle_request(
+    auth: &WsAuth,
+    peer_addr: Option<IpAddr>,
+    session: &Session,
+    text: &str,
+    joined: &mut Option<String>,
+) -> Result<(), ProxyError> {
+    let req = parse_request(text)?;
+    validation(&CONTEXT, auth, peer_addr, &req).await?;
+    if let Some(peer_id) = joined {
+        if peer_id != &req.peer_id {
+            return Err(ProxyError::RequestError("Peer id changed!"));
+        }
+    }
+
+    if let Some(answer) = req.answer {
+        return relay_answer(
+            auth.tid,
+            &req.info_hash,
+            &req.peer_id,
+            answer,
+            req.offer_id,
+            req.to_peer_id,
+        )
+        .await;
+    }
+
+    let event = parse_event(req.event.as_deref());
+    let announced = {
+        let mut swarms = SWARMS.lock().await;
+        let swarm = swarms.entry(auth.tid).or_default();
+        announce_in_swarm(swarm, auth.uid, session, &req, event)?
+    };
+    *joined = match event {
+        Some(Event::Stopped) => None,
+        _ => Some(req.peer_id.clone()),
+    };
+
+    let response = json!({
+        "action": "announce",
+        "interval": WS_INTERVAL,
+        "info_hash": req.info_hash,
+        "complete": announced.complete,
+        "incomplete": announced.incomplete,
+    });
+    session
+        .clone()
+        .text(response.to_string())
+        .await
+        .map_err(|_| ProxyError::RequestError("connection closed"))?;
+    let offers = req.offers.unwrap_or_default();
+    for (mut receiver, offer) in announced.receivers.into_iter().zip(offers.into_iter()) {
+        let msg = json!({
+            "action": "announce",
+            "offer": offer.offer,
+            "offer_id": offer.offer_id,
+            "peer_id": req.peer_id,
+            "info_hash": req.info_hash,
+        });
+        // receiver might have gone, it will be removed when its socket closed
+        let _ = receiver.text(msg.to_string()).await;
+    }
+
+    bypass_to_backend(AnnounceBypassData::new(
+        auth.uid,
+        auth.tid,
+        announced.upload,
+        announced.download,
+        req.left,
+        event,
+        unix_now(),
+    ));
+    Ok(())
+}
+
+async fn relay_answer(
+    tid: i64,
+    info_hash: &str,
+    peer_id: &str,
+    answer: Json,
+    offer_id: Option<String>,
+    to_peer_id: Option<String>,
+) -> Result<(), ProxyError> {
+    let (offer_id, to_peer_id) = match (offer_id, to_peer_id) {
+        (Some(offer_id), Some(to_peer_id)) => (offer_id, to_peer_id),
+        _ => return Err(ProxyError::RequestError("Incomplete answer!")),
+    };
+    let receiver = SWARMS
+        .lock()
+        .await
+        .get(&tid)
+        .and_then(|swarm| swarm.get(&to_peer_id))
+        .map(|p| p.session.clone());
+    if let Some(mut receiver) = receiver {
+        let msg = json!({
+            "action": "announce",
+            "answer": answer,
+            "offer_id": offer_id,
+            "peer_id": peer_id,
+            "info_hash": info_hash,
+        });
+        let _ = receiver.text(msg.to_string()).await;
+    }
+    Ok(())
+}
+
+async fn leave(tid: i64, peer_id: &str) {
+    leave_swarm(&mut *SWARMS.lock().await, tid, peer_id);
+}
+
+/// an empty swarm is removed as well
+fn leave_swarm<S>(swarms: &mut HashMap<i64, Swarm<S>>, tid: i64, peer_id: &str) {
+    if let Some(swarm) = swarms.get_mut(&tid) {
+        swarm.remove(peer_id);
+        if swarm.is_empty() {
+            swarms.remove(&tid);
+        }
+    }
+}
+
+/// close all sockets, their peers leave as sockets closed
+pub async fn close_all() {
+    let sessions: Vec<Session> = SWARMS
+        .lock()
+        .await
+        .values()
+        .flat_map(|swarm| swarm.values().map(|p| p.session.clone()))
+        .collect();
+    for session in sessions {
+        let _ = session.close(None).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    // actix_web::test comes with the glob import
+    use std::prelude::v1::test;
+
+    const WEB_PEER: &str = "-WW0100-abcdefghijkl";
+
+    /// info_hash as a binary string, 0x01 repeated
+    fn info_hash() -> String {
+        "\u{1}".repeat(20)
+    }
+
+    fn request(peer_id: &str, left: i64, uploaded: i64, offers: usize, event: &str) -> WsRequest {
+        let offers: Vec<Json> = (0..offers)
+            .map(|i| json!({"offer": {"type": "offer", "sdp": "v=0"}, "offer_id": i.to_string()}))
+            .collect();
+        let text = json!({
+            "action": "announce",
+            "info_hash": info_hash(),
+            "peer_id": peer_id,
+            "uploaded": uploaded,
+            "downloaded": 0,
+            "left": left,
+            "event": event,
+            "offers": offers,
+        });
+        parse_request(&text.to_string()).unwrap()
+    }
+
+    fn auth(passkey: &str) -> WsAuth {
+        WsAuth {
+            passkey: passkey.to_string(),
+            uid: 1,
+            tid: 1,
+        }
+    }
+
+    #[test]
+    fn parse_request_works() {
+        let req = request(WEB_PEER, 0, 10, 2, "started");
+        assert_eq!(req.peer_id, WEB_PEER);
+        assert_eq!(req.left, Some(0));
+        assert_eq!(req.offers.as_ref().map(Vec::len), Some(2));
+        assert!(matches!(
+            parse_event(req.event.as_deref()),
+            Some(Event::Started)
+        ));
+        assert!(parse_event(None).is_none());
+
+        let answer = json!({
+            "action": "announce",
+            "info_hash": info_hash(),
+            "peer_id": WEB_PEER,
+            "answer": {"type": "answer", "sdp": "v=0"},
+            "offer_id": "0",
+            "to_peer_id": "-WW0100-mnopqrstuvwx",
+        });
+        let req = parse_request(&answer.to_string()).unwrap();
+        assert!(req.answer.is_some());
+        assert_eq!(req.uploaded, 0);
+        assert_eq!(req.to_peer_id.as_deref(), Some("-WW0100-mnopqrstuvwx"));
+
+        let scrape = json!({"action": "scrape", "info_hash": info_hash(), "peer_id": WEB_PEER});
+        let err = parse_request(&scrape.to_string()).unwrap_err();
+        assert_eq!(err.reason(), "Only announce is supported!");
+        let err = parse_request("{\"action\":").unwrap_err();
+        assert_eq!(err.reason(), "malformed request");
+    }
+
+    #[test]
+    fn ws_info_hash_works() {
+        assert_eq!(ws_info_hash(&info_hash()).unwrap(), [1; 20]);
+        assert_eq!(ws_info_hash(&"\u{ff}".repeat(20)).unwrap(), [0xff; 20]);
+        assert!(ws_info_hash(&"\u{100}".repeat(20)).is_err());
+        assert!(ws_info_hash(&"\u{1}".repeat(19)).is_err());
+    }
+
+    #[tokio::test]
+    async fn validation_works() {
+        let ctx = Context::new("redis://127.0.0.1/");
+        ctx.filter.insert("passkey".to_string()).await;
+        ctx.update_ip_bans(&["1.2.3.0/24".to_string()], &[]).await;
+        ctx.info_hashes.write().await.insert(1, vec![[1; 20]]);
+        let addr = Some("8.8.8.8".parse().unwrap());
+        let req = request(WEB_PEER, 0, 0, 0, "started");
+        validation(&ctx, &auth("passkey"), addr, &req)
+            .await
+            .unwrap();
+
+        let err = validation(&ctx, &auth("unknown"), addr, &req)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.reason(),
+            "Passkey not found! Check your torrent please."
+        );
+        let banned = Some("1.2.3.4".parse().unwrap());
+        let err = validation(&ctx, &auth("passkey"), banned, &req)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), "Your address is banned!");
+        let req = request("-qB4500-abcdefghijkl", 0, 0, 0, "started");
+        let err = validation(&ctx, &auth("passkey"), addr, &req)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), "Client not allowed!");
+        let mut req = request(WEB_PEER, 0, 0, 0, "started");
+        req.info_hash = "\u{1}".repeat(19);
+        let err = validation(&ctx, &auth("passkey"), addr, &req)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), "Invalid info_hash!");
+    }
+
+    #[test]
+    fn announce_in_swarm_works() {
+        let seeder = "-WW0100-seederseeder";
+        let leecher = "-WW0100-leecherleech";
+        let mut swarm: Swarm<&str> = HashMap::new();
+
+        let req = request(seeder, 0, 100, 0, "started");
+        let ret = announce_in_swarm(&mut swarm, 1, &"seeder", &req, Some(Event::Started)).unwrap();
+        assert_eq!((ret.upload, ret.complete, ret.incomplete), (100, 1, 0));
+        assert!(ret.receivers.is_empty());
+
+        // offers of a leecher go to the seeder
+        let req = request(leecher, 1024, 0, 5, "started");
+        let ret = announce_in_swarm(&mut swarm, 2, &"leecher", &req, Some(Event::Started)).unwrap();
+        assert_eq!((ret.complete, ret.incomplete), (1, 1));
+        assert_eq!(ret.receivers, vec!["seeder"]);
+
+        // stats are reported in total, backend gets increments
+        let req = request(seeder, 0, 250, 5, "");
+        let ret = announce_in_swarm(&mut swarm, 1, &"seeder", &req, None).unwrap();
+        assert_eq!(ret.upload, 150);
+        assert_eq!(ret.receivers, vec!["leecher"]);
+
+        let req = request(seeder, 0, 250, 0, "");
+        let err = announce_in_swarm(&mut swarm, 3, &"thief", &req, None)
+            .err()
+            .unwrap();
+        assert_eq!(err.reason(), "Peer id already taken!");
+
+        // a completed leecher gets no offers from seeders
+        let req = request(leecher, 0, 0, 5, "completed");
+        let ret =
+            announce_in_swarm(&mut swarm, 2, &"leecher", &req, Some(Event::Completed)).unwrap();
+        assert_eq!((ret.complete, ret.incomplete), (2, 0));
+        assert!(ret.receivers.is_empty());
+
+        let req = request(leecher, 0, 0, 0, "stopped");
+        announce_in_swarm(&mut swarm, 2, &"leecher", &req, Some(Event::Stopped)).unwrap();
+        assert!(!swarm.contains_key(leecher));
+        assert!(swarm.contains_key(seeder));
+    }
+
+    #[test]
+    fn leave_swarm_works() {
+        let mut swarms: HashMap<i64, Swarm<&str>> = HashMap::new();
+        for (peer_id, session) in [("a", "a"), ("b", "b")] {
+            let req = request(peer_id, 0, 0, 0, "started");
+            let swarm = swarms.entry(1).or_default();
+            announce_in_swarm(swarm, 1, &session, &req, Some(Event::Started)).unwrap();
+        }
+        leave_swarm(&mut swarms, 1, "a");
+        assert_eq!(swarms[&1].len(), 1);
+        // leaving twice, or another torrent, is harmless
+        leave_swarm(&mut swarms, 1, "a");
+        leave_swarm(&mut swarms, 2, "b");
+        leave_swarm(&mut swarms, 1, "b");
+        assert!(swarms.is_empty());
+    }
+}
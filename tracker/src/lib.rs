@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate redis_module;
 
+use locality::{Locality, LOCALITY};
 use peerinfo::PeerInfo;
 use redis_module::{native_types::RedisType, Status};
 use redis_module::{raw, Context, RedisError, RedisResult, RedisValue};
@@ -9,6 +10,7 @@ use std::os::raw::c_void;
 use std::time::Duration;
 use std::{convert::TryFrom, str::FromStr};
 
+mod locality;
 mod peerinfo;
 mod seederinfo;
 mod util;
@@ -141,15 +143,25 @@ fn announce(ctx: &Context, args: Vec<String>) -> RedisResult {
         sm.delete(uid);
         response = RedisValue::SimpleStringStatic("?");
     } else {
-        sm.insert(uid, peer);
-        response = sm.gen_response(numwant);
+        sm.insert(uid, peer.clone());
+        response = sm.gen_response(numwant, &peer);
     }
     key.set_expire(Duration::from_secs(2700))?;
     Ok(response)
 }
 
-fn init(_: &Context, _: &Vec<String>) -> Status {
-    Status::Ok
+/* loadmodule <path> [LOCAL_CIDRS <cidr,...>] [LOCAL_RATIO <ratio>] */
+fn init(ctx: &Context, args: &Vec<String>) -> Status {
+    match Locality::from_args(args) {
+        Ok(locality) => {
+            *LOCALITY.write().unwrap() = locality;
+            Status::Ok
+        }
+        Err(e) => {
+            ctx.log_warning(&format!("invalid module arguments: {}", e));
+            Status::Err
+        }
+    }
 }
 
 redis_module! {
@@ -0,0 +1,143 @@
+use crate::peerinfo::PeerInfo;
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::sync::RwLock;
+
+/// Locality settings loaded from module arguments at init.
+pub(crate) static LOCALITY: RwLock<Locality> = RwLock::new(Locality::new());
+
+/// Subnets regarded as "local" (e.g. the campus IPv6 prefix).
+/// When a local peer announces, about `ratio` of the returned
+/// peers are picked from local ones and the rest from remote ones.
+#[derive(Debug)]
+pub struct Locality {
+    nets: Vec<IpNet>,
+    ratio: f64,
+}
+
+impl Locality {
+    pub const fn new() -> Self {
+        Self {
+            nets: Vec::new(),
+            ratio: 0.0,
+        }
+    }
+
+    /// Parse from module arguments, like
+    /// ```text
+    /// loadmodule libretracker.so LOCAL_CIDRS 2001:da8::/32,10.0.0.0/8 LOCAL_RATIO 0.8
+    /// ```
+    /// `LOCAL_RATIO` defaults to 0.5 once any cidr is given.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut locality = Self::new();
+        let mut ratio = None;
+        let mut iter = args.iter();
+        while let Some(key) = iter.next() {
+            let val = iter
+                .next()
+                .ok_or_else(|| format!("missing value for {}", key))?;
+            match key.to_ascii_uppercase().as_str() {
+                "LOCAL_CIDRS" => {
+                    for cidr in val.split(',').filter(|s| !s.is_empty()) {
+                        let net = cidr
+                            .trim()
+                            .parse::<IpNet>()
+                            .map_err(|_| format!("invalid cidr {}", cidr))?;
+                        locality.nets.push(net.trunc());
+                    }
+                }
+                "LOCAL_RATIO" => {
+                    let r: f64 = val.parse().map_err(|_| format!("invalid ratio {}", val))?;
+                    if !(0.0..=1.0).contains(&r) {
+                        return Err(format!("ratio {} out of [0, 1]", r));
+                    }
+                    ratio = Some(r);
+                }
+                _ => return Err(format!("unknown argument {}", key)),
+            }
+        }
+        locality.ratio = ratio.unwrap_or(0.5);
+        Ok(locality)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.nets.is_empty()
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+
+    pub fn is_local(&self, p: &PeerInfo) -> bool {
+        p.get_ipv4().is_some_and(|ip| self.contains(IpAddr::V4(ip)))
+            || p.get_ipv6().is_some_and(|ip| self.contains(IpAddr::V6(ip)))
+    }
+
+    /// how many local peers we want in a response of `num_want` peers
+    pub fn local_quota(&self, num_want: usize) -> usize {
+        (num_want as f64 * self.ratio).round() as usize
+    }
+}
+
+impl Default for Locality {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locality;
+    use crate::peerinfo::PeerInfo;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let l = Locality::from_args(&args(
+            "LOCAL_CIDRS 2001:da8::/32,10.0.0.0/8 LOCAL_RATIO 0.8",
+        ));
+        assert!(l.is_ok());
+        let l = l.unwrap();
+        assert!(l.is_enabled());
+        assert_eq!(l.local_quota(50), 40);
+
+        let l = Locality::from_args(&args("local_cidrs 10.0.0.1/8")).unwrap();
+        assert_eq!(l.local_quota(50), 25);
+
+        let l = Locality::from_args(&[]).unwrap();
+        assert!(!l.is_enabled());
+    }
+
+    #[test]
+    fn test_parse_bad_args() {
+        assert!(Locality::from_args(&args("LOCAL_CIDRS 10.0.0.0/33")).is_err());
+        assert!(Locality::from_args(&args("LOCAL_CIDRS 10.0.0.0/8 LOCAL_RATIO 1.5")).is_err());
+        assert!(Locality::from_args(&args("LOCAL_RATIO")).is_err());
+        assert!(Locality::from_args(&args("FOO BAR")).is_err());
+    }
+
+    #[test]
+    fn test_is_local() {
+        let l = Locality::from_args(&args("LOCAL_CIDRS 2001:da8::/32,10.0.0.0/8")).unwrap();
+        let v4 = PeerInfo::from(Some("10.1.2.3".parse().unwrap()), None, 1);
+        let v6 = PeerInfo::from(None, Some("2001:da8:1::1".parse().unwrap()), 1);
+        let both = PeerInfo::from(
+            Some("1.1.1.1".parse().unwrap()),
+            Some("2001:da8:1::1".parse().unwrap()),
+            1,
+        );
+        let remote = PeerInfo::from(
+            Some("1.1.1.1".parse().unwrap()),
+            Some("2400:da8::1".parse().unwrap()),
+            1,
+        );
+        assert!(l.is_local(&v4));
+        assert!(l.is_local(&v6));
+        assert!(l.is_local(&both));
+        assert!(!l.is_local(&remote));
+        assert!(!l.is_local(&PeerInfo::new()));
+    }
+}
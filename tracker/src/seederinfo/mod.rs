@@ -1,6 +1,7 @@
 use super::*;
 mod seederarray;
 mod seedermap;
+use locality::{Locality, LOCALITY};
 use peerinfo::PeerInfo;
 use seederarray::SeederArray;
 pub use seedermap::SeederMap;
@@ -39,6 +40,18 @@ impl Default for Bucket {
     }
 }
 
+/// append a peer in compact format(BEP 23 and BEP 7)
+fn encode_peer(p: &PeerInfo, peers: &mut Vec<u8>, peers6: &mut Vec<u8>) {
+    if let Some(ref v4) = p.get_ipv4() {
+        peers.extend_from_slice(&v4.octets());
+        peers.extend_from_slice(&p.get_port().to_be_bytes());
+    };
+    if let Some(v6) = p.get_ipv6() {
+        peers6.extend_from_slice(&v6.octets());
+        peers6.extend_from_slice(&p.get_port().to_be_bytes());
+    };
+}
+
 pub enum SeederInfo {
     InlineSeeder(SeederArray),
     MulitSeeder(SeederMap),
@@ -63,9 +76,12 @@ impl SeederInfo {
         }
     }
 
-    pub fn gen_response(&self, num_want: usize) -> RedisValue {
+    pub fn gen_response(&self, num_want: usize, requester: &PeerInfo) -> RedisValue {
         let (peers, peers6) = match self {
-            SeederInfo::MulitSeeder(sm) => sm.gen_response(num_want),
+            SeederInfo::MulitSeeder(sm) => {
+                let locality = LOCALITY.read().unwrap();
+                sm.gen_response(num_want, requester, &locality)
+            }
             SeederInfo::InlineSeeder(sa) => sa.gen_response(),
         };
         RedisValue::Array(vec![
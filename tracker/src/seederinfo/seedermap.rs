@@ -1,4 +1,5 @@
 use indexmap::IndexMap;
+use rand::seq::index;
 use rand::Rng;
 
 use std::usize;
@@ -8,6 +9,9 @@ use super::*;
 
 type HashTable = IndexMap<Key, Value>;
 
+/// how many candidates are sampled for each wanted peer
+const SAMPLE_FACTOR: usize = 4;
+
 type SeederMapIter<'a> = std::iter::Chain<
     indexmap::map::Iter<'a, u64, peerinfo::PeerInfo>,
     indexmap::map::Iter<'a, u64, peerinfo::PeerInfo>,
@@ -101,7 +105,56 @@ impl SeederMap {
         }
     }
 
-    pub fn gen_response(&self, num_want: usize) -> (Vec<u8>, Vec<u8>) {
+    /// Pick up to `num_want` peers. If locality is configured and the
+    /// requester is local, about `ratio` of them will be local peers,
+    /// the remaining slots are filled by remote ones (and vice versa
+    /// when either side is not enough). Only a random sample of
+    /// `num_want * SAMPLE_FACTOR` peers is checked, never the whole swarm.
+    pub fn gen_response(
+        &self,
+        num_want: usize,
+        requester: &PeerInfo,
+        locality: &Locality,
+    ) -> (Vec<u8>, Vec<u8>) {
+        if !locality.is_enabled() || !locality.is_local(requester) {
+            return self.gen_random_response(num_want);
+        }
+        let mut buf_peer: Vec<u8> = Vec::with_capacity(num_want * 6);
+        let mut buf_peer6: Vec<u8> = Vec::with_capacity(num_want * 18);
+        let (local, remote): (Vec<&PeerInfo>, Vec<&PeerInfo>) = self
+            .sample(num_want.saturating_mul(SAMPLE_FACTOR))
+            .into_iter()
+            .partition(|p| locality.is_local(p));
+        let local_want = locality.local_quota(num_want).min(local.len());
+        let remote_want = (num_want - local_want).min(remote.len());
+        let local_want = (num_want - remote_want).min(local.len());
+
+        // the sample is already in random order
+        for p in local.iter().take(local_want) {
+            encode_peer(p, &mut buf_peer, &mut buf_peer6);
+        }
+        for p in remote.iter().take(remote_want) {
+            encode_peer(p, &mut buf_peer, &mut buf_peer6);
+        }
+        (buf_peer, buf_peer6)
+    }
+
+    /// up to `amount` distinct peers in random order, O(amount)
+    fn sample(&self, amount: usize) -> Vec<&PeerInfo> {
+        let (mit, iit) = (self.get_mit(), self.get_iit());
+        let peer_cnt = mit.len() + iit.len();
+        let mut rng = rand::thread_rng();
+        index::sample(&mut rng, peer_cnt, amount.min(peer_cnt))
+            .into_iter()
+            .filter_map(|i| match i.checked_sub(mit.len()) {
+                None => mit.get_index(i),
+                Some(i) => iit.get_index(i),
+            })
+            .map(|(_, p)| p)
+            .collect()
+    }
+
+    fn gen_random_response(&self, num_want: usize) -> (Vec<u8>, Vec<u8>) {
         let mut buf_peer: Vec<u8> = Vec::with_capacity(num_want * 6);
         let mut buf_peer6: Vec<u8> = Vec::with_capacity(num_want * 18);
         let peer_cnt = self.map[0].len() + self.map[1].len();
@@ -119,14 +172,7 @@ impl SeederMap {
             .skip(rand)
             .take(num_want);
         while let Some((_, p)) = iter.next() {
-            if let Some(ref v4) = p.get_ipv4() {
-                buf_peer.extend_from_slice(&v4.octets());
-                buf_peer.extend_from_slice(&p.get_port().to_be_bytes());
-            };
-            if let Some(v6) = p.get_ipv6() {
-                buf_peer6.extend_from_slice(&v6.octets());
-                buf_peer6.extend_from_slice(&p.get_port().to_be_bytes());
-            };
+            encode_peer(p, &mut buf_peer, &mut buf_peer6);
         }
         (buf_peer, buf_peer6)
     }
@@ -139,9 +185,41 @@ impl SeederMap {
 #[cfg(test)]
 mod tests {
     use crate::{
+        locality::Locality,
         peerinfo::PeerInfo,
         seederinfo::{seederarray::SeederArray, SeederMap},
     };
+    use std::net::Ipv4Addr;
+
+    /// `local` peers in 10.0.0.0/8 and `remote` peers in 172.16.0.0/12
+    fn synthetic_swarm(local: u32, remote: u32) -> SeederMap {
+        let mut sm = SeederMap::new();
+        for i in 0..local {
+            let ip = Ipv4Addr::from(0x0a00_0000 + i);
+            sm.insert(i as u64, &PeerInfo::from(Some(ip), None, 1));
+        }
+        for i in 0..remote {
+            let ip = Ipv4Addr::from(0xac10_0000 + i);
+            sm.insert((local + i) as u64, &PeerInfo::from(Some(ip), None, 1));
+        }
+        sm
+    }
+
+    /// returns (local, remote) count in compact peers
+    fn count_peers(peers: &[u8]) -> (usize, usize) {
+        let local = peers.chunks(6).filter(|p| p[0] == 10).count();
+        (local, peers.len() / 6 - local)
+    }
+
+    fn campus_locality(ratio: &str) -> Locality {
+        let args: Vec<String> = vec![
+            "LOCAL_CIDRS".into(),
+            "10.0.0.0/8".into(),
+            "LOCAL_RATIO".into(),
+            ratio.into(),
+        ];
+        Locality::from_args(&args).unwrap()
+    }
 
     #[test]
     fn check_struct_size() {
@@ -174,6 +252,68 @@ mod tests {
         assert!(sm.get_mit().get(&5).is_none());
     }
 
+    #[test]
+    fn test_local_ratio() {
+        let sm = synthetic_swarm(100, 100);
+        let me = PeerInfo::from(Some(Ipv4Addr::new(10, 9, 9, 9)), None, 1);
+        let (peers, peers6) = sm.gen_response(20, &me, &campus_locality("0.8"));
+        assert_eq!(count_peers(&peers), (16, 4));
+        assert!(peers6.is_empty());
+        let (peers, _) = sm.gen_response(20, &me, &campus_locality("0"));
+        assert_eq!(count_peers(&peers), (0, 20));
+    }
+
+    #[test]
+    fn test_local_backfill() {
+        let me = PeerInfo::from(Some(Ipv4Addr::new(10, 9, 9, 9)), None, 1);
+        let locality = campus_locality("0.8");
+        // not enough local peers, fill with remote ones
+        let sm = synthetic_swarm(5, 100);
+        let (peers, _) = sm.gen_response(50, &me, &locality);
+        assert_eq!(count_peers(&peers), (5, 45));
+        // not enough remote peers, fill with local ones
+        let sm = synthetic_swarm(100, 3);
+        let (peers, _) = sm.gen_response(50, &me, &locality);
+        assert_eq!(count_peers(&peers), (47, 3));
+        // fewer peers than wanted
+        let sm = synthetic_swarm(10, 10);
+        let (peers, _) = sm.gen_response(50, &me, &locality);
+        assert_eq!(count_peers(&peers), (10, 10));
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut sm = synthetic_swarm(1000, 1000);
+        // move half of them to the other map
+        sm.time_to_compaction = 0;
+        sm.compaction();
+        for i in 0..1000 {
+            sm.insert(
+                i,
+                &PeerInfo::from(Some(Ipv4Addr::from(0x0a00_0000 + i as u32)), None, 1),
+            );
+        }
+        let sample = sm.sample(80);
+        assert_eq!(sample.len(), 80);
+        let mut ips: Vec<_> = sample.iter().map(|p| p.get_ipv4()).collect();
+        ips.sort();
+        ips.dedup();
+        assert_eq!(ips.len(), 80);
+        // never more than the swarm
+        let sm = synthetic_swarm(3, 4);
+        assert_eq!(sm.sample(80).len(), 7);
+    }
+
+    #[test]
+    fn test_remote_requester() {
+        let sm = synthetic_swarm(100, 100);
+        let me = PeerInfo::from(Some(Ipv4Addr::new(8, 8, 8, 8)), None, 1);
+        let (peers, _) = sm.gen_response(20, &me, &campus_locality("0.8"));
+        assert_eq!(peers.len(), 20 * 6);
+        let (peers, _) = sm.gen_response(20, &me, &Locality::new());
+        assert_eq!(peers.len(), 20 * 6);
+    }
+
     #[test]
     fn test_compaction() {
         let v = PeerInfo::default();